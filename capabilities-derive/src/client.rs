@@ -0,0 +1,136 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{Ident, Lit, LitStr, Meta, NestedMeta, Path};
+
+use crate::dto::{get_new_struct, get_patch_struct};
+use crate::helpers::IdFields;
+use crate::query::get_query_struct;
+
+const CLIENT: &str = "client";
+
+pub fn parse_field_args_for_client(attr_args: &Vec<NestedMeta>) -> Option<LitStr> {
+    let mut client = None;
+    for i in attr_args {
+        if let NestedMeta::Meta(Meta::NameValue(nv)) = i {
+            if !nv.path.is_ident(CLIENT) {
+                continue;
+            }
+            match &nv.lit {
+                Lit::Str(path) => client = Some(path.to_owned()),
+                lit => lit
+                    .span()
+                    .unstable()
+                    .error("client takes the path of the resource, like \"/orders\"")
+                    .emit(),
+            }
+        }
+    }
+    client
+}
+
+/// Generates `<Struct>Client`, the remote mirror of the capabilities of the struct.
+///
+/// Read, Update and Delete address a single item, so they are only generated when the struct has an id.
+/// The parts of a composite id become percent-encoded segments of the path.
+pub fn impl_remote_client(
+    path: &LitStr,
+    struct_id: &Ident,
+    caps: &[&Path],
+    id: Option<&IdFields>,
+    query: bool,
+    dto: bool,
+) -> TokenStream2 {
+    let client_id = format_ident!("{}Client", struct_id);
+    let typealias = format_ident!("{}Id", struct_id);
+    let (create_input, update_input) = match dto {
+        true => (get_new_struct(struct_id), get_patch_struct(struct_id)),
+        false => (struct_id.clone(), struct_id.clone()),
+    };
+
+    // The server answers reads with the projection, `XView` if the struct has sensitive fields.
+    let view = quote! { <#struct_id as ::capabilities::Projection>::View };
+    let mut methods = vec![];
+    for cap in caps {
+        let cap = match cap.get_ident() {
+            Some(cap) => cap.to_string(),
+            None => continue,
+        };
+        let method = match (cap.as_str(), id) {
+            ("Read", Some(id)) => {
+                let path = id.segments(quote! { id }, true);
+                quote! {
+                    pub async fn read(&self, id: #typealias) -> Result<#view, ::capabilities::CapServiceError> {
+                        self.remote.read(#path).await
+                    }
+                }
+            }
+            ("ReadAll", _) if query => {
+                let query_struct = get_query_struct(struct_id);
+                quote! {
+                    pub async fn read_all(&self, query: &#query_struct) -> Result<::capabilities::Page<#view>, ::capabilities::CapServiceError> {
+                        self.remote.read_page(query).await
+                    }
+                }
+            }
+            ("ReadAll", _) => quote! {
+                pub async fn read_all(&self) -> Result<Vec<#view>, ::capabilities::CapServiceError> {
+                    self.remote.read_all().await
+                }
+            },
+            ("Create", _) => quote! {
+                pub async fn create(&self, data: #create_input) -> Result<#struct_id, ::capabilities::CapServiceError> {
+                    self.remote.create(&data).await
+                }
+            },
+            ("Update", Some(id)) => {
+                let path = id.segments(quote! { data }, false);
+                quote! {
+                    pub async fn update(&self, data: #update_input) -> Result<(), ::capabilities::CapServiceError> {
+                        self.remote.update(#path, &data).await
+                    }
+                }
+            }
+            ("UpdateAll", _) => quote! {
+                pub async fn update_all(&self, data: Vec<#struct_id>) -> Result<(), ::capabilities::CapServiceError> {
+                    self.remote.update_all(&data).await
+                }
+            },
+            ("Delete", Some(id)) => {
+                let path = id.segments(quote! { id }, true);
+                quote! {
+                    pub async fn delete(&self, id: #typealias) -> Result<(), ::capabilities::CapServiceError> {
+                        self.remote.delete(#path).await
+                    }
+                }
+            }
+            ("DeleteAll", _) => quote! {
+                pub async fn delete_all(&self, data: Vec<#struct_id>) -> Result<(), ::capabilities::CapServiceError> {
+                    self.remote.delete_all(&data).await
+                }
+            },
+            _ => continue,
+        };
+        methods.push(method);
+    }
+
+    quote! {
+        #[derive(Debug, Clone)]
+        pub struct #client_id {
+            remote: ::capabilities::RemoteClient,
+        }
+
+        impl #client_id {
+            pub fn new(service: ::capabilities::WebService) -> Self {
+                Self {
+                    remote: ::capabilities::RemoteClient::new(service, #path),
+                }
+            }
+
+            pub fn remote(&self) -> &::capabilities::RemoteClient {
+                &self.remote
+            }
+
+            #( #methods )*
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{Ident, Lit, LitStr, Meta, NestedMeta};
+
+use crate::helpers::IdFields;
+
+const COLLECTION: &str = "collection";
+
+pub fn parse_field_args_for_collection(attr_args: &Vec<NestedMeta>) -> Option<LitStr> {
+    let mut collection = None;
+    for i in attr_args {
+        if let NestedMeta::Meta(Meta::NameValue(nv)) = i {
+            if !nv.path.is_ident(COLLECTION) {
+                continue;
+            }
+            match &nv.lit {
+                Lit::Str(name) => collection = Some(name.to_owned()),
+                lit => lit
+                    .span()
+                    .unstable()
+                    .error("collection takes the name of the directory keeping the documents")
+                    .emit(),
+            }
+        }
+    }
+    collection
+}
+
+/// `impl capabilities::Document` for the struct and `capabilities::Locator` for it and its id struct.
+/// The parts of a composite id are joined into the key by `capabilities::composite_key`.
+pub fn impl_document(
+    collection: &LitStr,
+    struct_id: &Ident,
+    id: Option<&IdFields>,
+) -> TokenStream2 {
+    let id = match id {
+        Some(id) => id,
+        None => {
+            collection
+                .span()
+                .unstable()
+                .error("Documents are keyed by their id, add id = \"field\"")
+                .emit();
+            return quote! {};
+        }
+    };
+    let typealias = format_ident!("{}Id", struct_id);
+    let parts = id.segments(quote! { self }, false);
+    let id_parts = id.segments(quote! { self }, true);
+
+    quote! {
+        impl ::capabilities::Locator for #struct_id {
+            type Document = #struct_id;
+
+            fn key(&self) -> String {
+                ::capabilities::composite_key(#parts)
+            }
+
+            fn document(&self) -> Option<&Self::Document> {
+                Some(self)
+            }
+        }
+
+        impl ::capabilities::Locator for #typealias {
+            type Document = #struct_id;
+
+            fn key(&self) -> String {
+                ::capabilities::composite_key(#id_parts)
+            }
+        }
+
+        impl ::capabilities::Document for #struct_id {
+            const COLLECTION: &'static str = #collection;
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Fields, FnArg, Ident, Item, ItemStruct, Meta, NestedMeta, Path, Signature, Type,
+};
+
+use crate::helpers::get_snake_case;
+
+pub const DTO: &str = "dto";
+const CAP_ATTR: &str = "cap";
+const SKIP_CREATE: &str = "skip_create";
+const SKIP_UPDATE: &str = "skip_update";
+const GRANT: &str = "grant";
+const SENSITIVE: &str = "sensitive";
+
+/// The `dto` flag of `#[capabilities(Create, Update, dto)]`.
+pub fn parse_field_args_for_dto(attr_args: &[NestedMeta]) -> Option<Path> {
+    attr_args.iter().find_map(|i| match i {
+        NestedMeta::Meta(Meta::Path(p)) if p.is_ident(DTO) => Some(p.clone()),
+        _ => None,
+    })
+}
+
+/// `NewPerson`, what `Create` takes with `dto`.
+pub fn get_new_struct(struct_name: &Ident) -> Ident {
+    format_ident!("New{}", struct_name)
+}
+
+/// `PersonPatch`, what `Update` takes with `dto`.
+pub fn get_patch_struct(struct_name: &Ident) -> Ident {
+    format_ident!("{}Patch", struct_name)
+}
+
+/// `NewX` or `XPatch` when a capability fn takes one of them.
+pub fn get_dto_input(sig: &Signature, item_struct: &Ident) -> Option<Ident> {
+    let segment = match sig.inputs.first() {
+        Some(FnArg::Typed(t)) => match t.ty.as_ref() {
+            Type::Path(p) => p.path.segments.last()?,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    [get_new_struct(item_struct), get_patch_struct(item_struct)]
+        .into_iter()
+        .find(|dto| segment.ident == *dto)
+}
+
+/// `#[cap(skip_create, skip_update, grant, sensitive)]` of a field.
+#[derive(Default)]
+pub struct FieldOptions {
+    pub skip_create: bool,
+    pub skip_update: bool,
+    /// A grant of the single field, like `update:orders.status`, lets a patch change it.
+    pub grant: bool,
+    /// Only shown with a grant like `read:person.personnummer`, see `impl_projection`.
+    pub sensitive: bool,
+}
+
+impl FieldOptions {
+    /// The options that are about `NewX` and `XPatch`.
+    fn needs_dto(&self) -> bool {
+        self.skip_create || self.skip_update || self.grant
+    }
+}
+
+pub fn is_cap_attr(attr: &Attribute) -> bool {
+    attr.path.is_ident(CAP_ATTR)
+}
+
+pub fn parse_field_options(attrs: &[Attribute]) -> FieldOptions {
+    let mut options = FieldOptions::default();
+    for attr in attrs.iter().filter(|a| is_cap_attr(a)) {
+        let nested = match attr.parse_meta() {
+            Ok(Meta::List(list)) => list.nested,
+            _ => {
+                attr.span()
+                    .unstable()
+                    .error("Expected #[cap(...)] with skip_create, skip_update, grant or sensitive")
+                    .emit();
+                continue;
+            }
+        };
+        for n in nested {
+            match n {
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident(SKIP_CREATE) => {
+                    options.skip_create = true
+                }
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident(SKIP_UPDATE) => {
+                    options.skip_update = true
+                }
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident(GRANT) => options.grant = true,
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident(SENSITIVE) => {
+                    options.sensitive = true
+                }
+                n => n
+                    .span()
+                    .unstable()
+                    .error("cap takes skip_create, skip_update, grant and sensitive")
+                    .emit(),
+            }
+        }
+    }
+    options
+}
+
+/// Removes the `#[cap(...)]` attributes of the fields, they are only read by `#[capabilities]`.
+/// Without `dto` only `sensitive` has something to apply to, `impl_dto` reads them otherwise.
+pub fn strip_cap_attrs(item: &mut Item, dto: bool) {
+    let fields = match item {
+        Item::Struct(s) => &mut s.fields,
+        _ => return,
+    };
+    for f in fields.iter_mut() {
+        if !dto && parse_field_options(&f.attrs).needs_dto() {
+            f.span()
+                .unstable()
+                .error("skip_create, skip_update and grant apply to the structs generated by dto, add dto")
+                .emit();
+        }
+        f.attrs.retain(|a| !is_cap_attr(a));
+    }
+}
+
+/// `NewX` without the id and the `#[cap(skip_create)]` fields and `XPatch` with the id and
+/// every other field as an `Option`, leaving out the `#[cap(skip_update)]` fields. Both derive
+/// what the struct derives and convert from it. `XPatch::check` denies a patch changing a field
+/// the grant does not cover, only `#[cap(grant)]` fields can be granted one by one.
+pub fn impl_dto(dto: &Path, item_struct: &ItemStruct, id_fields: &[Ident]) -> TokenStream2 {
+    if !matches!(item_struct.fields, Fields::Named(_)) {
+        dto.span()
+            .unstable()
+            .error("dto needs a struct with named fields")
+            .emit();
+        return quote! {};
+    }
+
+    let struct_name = &item_struct.ident;
+    let new_struct = get_new_struct(struct_name);
+    let patch_struct = get_patch_struct(struct_name);
+    let resource = get_snake_case(struct_name);
+    let into_item = format_ident!("into_{}", resource);
+    let derives: Vec<&Attribute> = item_struct
+        .attrs
+        .iter()
+        .filter(|a| a.path.is_ident("derive"))
+        .collect();
+    let derives_default = derives.iter().any(|a| {
+        a.tokens
+            .to_string()
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|d| d == "Default")
+    });
+    let patch_default = (!derives_default).then(|| quote! { #[derive(Default)] });
+
+    let mut new_fields = vec![];
+    let mut new_idents = vec![];
+    let mut omitted = vec![];
+    let mut omitted_types = vec![];
+    let mut patch_fields = vec![];
+    let mut patched = vec![];
+    let mut patched_names = vec![];
+    let mut grantable = vec![];
+    let mut id_idents = vec![];
+    for f in &item_struct.fields {
+        let ident = f.ident.as_ref().unwrap();
+        let ty = &f.ty;
+        let options = parse_field_options(&f.attrs);
+        let attrs: Vec<&Attribute> = f.attrs.iter().filter(|a| !is_cap_attr(a)).collect();
+        let is_id = id_fields.contains(ident);
+
+        if is_id || options.skip_create {
+            omitted.push(ident);
+            omitted_types.push(ty);
+        } else {
+            new_fields.push(quote! { #( #attrs )* pub #ident: #ty });
+            new_idents.push(ident);
+        }
+
+        if is_id {
+            patch_fields.push(quote! { #( #attrs )* pub #ident: #ty });
+            id_idents.push(ident);
+        } else if !options.skip_update {
+            patch_fields.push(quote! { #( #attrs )* pub #ident: Option<#ty> });
+            patched.push(ident);
+            patched_names.push(ident.to_string());
+            grantable.push(options.grant);
+        } else if options.grant {
+            f.span()
+                .unstable()
+                .error("A #[cap(skip_update)] field cannot be granted")
+                .emit();
+        }
+    }
+
+    quote! {
+        #( #derives )*
+        pub struct #new_struct {
+            #( #new_fields, )*
+        }
+
+        impl #new_struct {
+            /// The item with the fields the caller does not choose, like the id.
+            pub fn #into_item(self, #( #omitted: #omitted_types ),*) -> #struct_name {
+                #struct_name {
+                    #( #new_idents: self.#new_idents, )*
+                    #( #omitted, )*
+                }
+            }
+        }
+
+        impl From<#struct_name> for #new_struct {
+            fn from(item: #struct_name) -> Self {
+                #new_struct {
+                    #( #new_idents: item.#new_idents, )*
+                }
+            }
+        }
+
+        #( #derives )*
+        #patch_default
+        pub struct #patch_struct {
+            #( #patch_fields, )*
+        }
+
+        impl #patch_struct {
+            /// Sets the fields of the item the patch has a value for.
+            #[allow(unused_variables)]
+            pub fn apply(self, item: &mut #struct_name) {
+                #(
+                    if let Some(v) = self.#patched {
+                        item.#patched = v;
+                    }
+                )*
+            }
+
+            /// Denies the patch if the grant does not cover each field it has a value for.
+            #[allow(unused_variables)]
+            pub fn check(&self, grant: &::capabilities::Grant) -> Result<(), ::capabilities::CapServiceError> {
+                #(
+                    if self.#patched.is_some() {
+                        grant.check_field(#resource, #patched_names, #grantable)?;
+                    }
+                )*
+                Ok(())
+            }
+        }
+
+        impl From<#struct_name> for #patch_struct {
+            fn from(item: #struct_name) -> Self {
+                #patch_struct {
+                    #( #id_idents: item.#id_idents, )*
+                    #( #patched: Some(item.#patched), )*
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,53 @@
 use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{Delimiter, Group, Literal, TokenStream as TokenStream2, TokenTree};
 use quote::{format_ident, quote};
-use syn::{Ident, Item, ItemStruct, Lit, Meta, MetaNameValue, NestedMeta, Type};
+use syn::spanned::Spanned;
+use syn::{
+    parse_quote, Block, FnArg, GenericArgument, GenericParam, Generics, Ident, Index, Item, ItemStruct, Lit,
+    LitStr, Member, Meta, MetaNameValue, NestedMeta, Path, PathArguments, ReturnType, Signature,
+    Type,
+};
+
+use crate::dto::{get_new_struct, get_patch_struct, DTO};
+use crate::query::QUERY;
 
 #[allow(dead_code)]
 const FIELD_NAME: &str = "con";
+const POOL_MAX_CONNECTIONS: &str = "max_connections";
+const POOL_MIN_CONNECTIONS: &str = "min_connections";
+const POOL_ACQUIRE_TIMEOUT: &str = "acquire_timeout";
+const POOL_IDLE_TIMEOUT: &str = "idle_timeout";
+const POOL_LAZY: &str = "lazy";
+const POOL_WAL: &str = "wal";
+const POOL_FOREIGN_KEYS: &str = "foreign_keys";
+const POOL_CONNECT_RETRIES: &str = "connect_retries";
+const MIGRATIONS: &str = "migrations";
+const WEB_BASE_URL: &str = "base_url";
+const WEB_TIMEOUT: &str = "timeout";
+const WEB_CONNECT_TIMEOUT: &str = "connect_timeout";
+const WEB_USER_AGENT: &str = "user_agent";
+const WEB_HEADER: &str = "header";
+const WEB_ACCEPT_INVALID_CERTS: &str = "accept_invalid_certs";
+const WEB_TOKEN_ENV: &str = "token_env";
+const KEY: &str = "key";
+const ROUTE: &str = "route";
+const ROUTE_PRIMARY: &str = "primary";
+const ROUTE_REPLICA: &str = "replica";
+const VERB_INPUT: &str = "input";
+const VERB_OUTPUT: &str = "output";
+const BUILTIN_CAPS: [&str; 11] = [
+    "Create",
+    "CreateAll",
+    "Read",
+    "Update",
+    "Delete",
+    "ReadAll",
+    "StreamAll",
+    "UpdateAll",
+    "DeleteAll",
+    "Publish",
+    "Consume",
+];
 
 fn get_id_identifier() -> Ident {
     format_ident!("{}", "id")
@@ -14,12 +57,12 @@ fn get_name_identifier() -> Ident {
     format_ident!("{}", "name")
 }
 
-fn get_cap_macro() -> TokenStream2 {
+pub fn get_cap_macro() -> TokenStream2 {
     quote! {
         macro_rules! cap {
         ($name:ident for $type:ty, composing $({$operation:ty, $d:ty, $e:ty}),+) => {
             #[async_trait]
-            pub trait $name: $(CapabilityTrait<$operation, Data = $d, Error = $e>+)+ {}
+            pub trait $name: ::capabilities::Observe + $(CapabilityTrait<$operation, Data = $d, Error = $e>+)+ {}
 
             #[async_trait]
             impl $name for $type {}
@@ -27,6 +70,236 @@ fn get_cap_macro() -> TokenStream2 {
     }}
 }
 
+/// The check every capability function makes before it performs, see `get_service_traits`.
+pub fn get_guard_code(item_cap: &Ident) -> TokenStream2 {
+    get_operation_guard_code(quote! { ::capabilities::#item_cap })
+}
+
+/// The guard of a `Read`, `ReadAll` or `StreamAll`, handing out the `capabilities::Projection` of
+/// what it reads. The fn takes `grant: impl Into<::capabilities::Grant>`.
+pub fn get_projection_guard_code(item_cap: &Ident, output: TokenStream2) -> TokenStream2 {
+    let guard = get_guard_code(item_cap);
+    quote! {
+        let grant: ::capabilities::Grant = grant.into();
+        let cap = grant.capability;
+        let output: Result<#output, CapServiceError> = async {
+            #guard
+        }
+        .await;
+        Ok(::capabilities::Projection::project(output?, &grant))
+    }
+}
+
+/// The guard of an operation that is not one of `capabilities`, like the ones of custom verbs.
+pub fn get_operation_guard_code(operation: TokenStream2) -> TokenStream2 {
+    quote! {
+        let valid = #operation { data: param };
+        __capabilities::guard(service, valid, cap).await
+    }
+}
+
+/// The `perform` of a capability function. The body becomes a method of `CapService`, so that
+/// it runs on the service `capabilities::Routing` picks for the operation.
+pub fn get_perform_code(
+    fn_signature: &Ident,
+    operation: TokenStream2,
+    data: TokenStream2,
+    data_accessor: TokenStream2,
+    fn_block: &Block,
+    route: &TokenStream2,
+    generics: &Generics,
+) -> TokenStream2 {
+    let inner = format_ident!("perform_{}", fn_signature);
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    quote! {
+        impl CapService {
+            #[allow(unused_variables)]
+            async fn #inner #impl_generics(
+                &self,
+                action: #operation,
+                _guard: __capabilities::Guard,
+            ) -> Result<#data, CapServiceError> #where_clause {
+                #data_accessor
+                #fn_block
+            }
+        }
+
+        #[async_trait]
+        impl #impl_generics CapabilityTrait<#operation> for CapService #where_clause {
+            type Data = #data;
+            type Error = CapServiceError;
+
+            async fn perform(
+                &self,
+                action: #operation,
+                guard: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error> {
+                ::capabilities::Observe::performing(self, &action, action.into_enum());
+                let service = ::capabilities::Routing::route(self, action.into_enum(), #route);
+                service.#inner(action, guard).await
+            }
+        }
+    }
+}
+
+/// The generic parameters and where predicates of a capability fn, they go ahead of the
+/// `Service` of its guard fn.
+pub fn get_guard_generics(generics: &Generics) -> (TokenStream2, TokenStream2) {
+    let params = generics.params.iter();
+    let predicates = generics
+        .where_clause
+        .iter()
+        .flat_map(|w| w.predicates.iter());
+    (quote! { #( #params, )* }, quote! { #( #predicates, )* })
+}
+
+/// `T` of the `Result<T, CapServiceError>` a capability fn returns.
+pub fn get_result_type(sig: &Signature) -> Option<&Type> {
+    let segment = match &sig.output {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::Path(p) => p.path.segments.last()?,
+            _ => return None,
+        },
+        ReturnType::Default => return None,
+    };
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|a| match a {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// The arguments of `item_struct` in the signature of a capability fn, `<T>` for an input
+/// `Wrapper<T>` or an output `Result<Vec<Wrapper<T>>, _>`.
+pub fn get_struct_arguments(sig: &Signature, item_struct: &Ident) -> PathArguments {
+    let inputs = sig.inputs.iter().filter_map(|i| match i {
+        FnArg::Typed(t) => Some(t.ty.as_ref()),
+        FnArg::Receiver(_) => None,
+    });
+    let output = match &sig.output {
+        ReturnType::Type(_, ty) => Some(ty.as_ref()),
+        ReturnType::Default => None,
+    };
+    inputs
+        .chain(output)
+        .find_map(|ty| find_struct_arguments(ty, item_struct))
+        .unwrap_or(PathArguments::None)
+}
+
+fn find_struct_arguments(ty: &Type, item_struct: &Ident) -> Option<PathArguments> {
+    match ty {
+        Type::Path(p) => {
+            let segment = p.path.segments.last()?;
+            if segment.ident == *item_struct {
+                return Some(segment.arguments.clone());
+            }
+            match &segment.arguments {
+                PathArguments::AngleBracketed(args) => args.args.iter().find_map(|a| match a {
+                    GenericArgument::Type(ty) => find_struct_arguments(ty, item_struct),
+                    _ => None,
+                }),
+                _ => None,
+            }
+        }
+        Type::Reference(r) => find_struct_arguments(&r.elem, item_struct),
+        _ => None,
+    }
+}
+
+/// The traits of a `CapService` and the guard of its capability functions. Only the guard of a
+/// `MockService` lets the service observe the calls and script their results, see
+/// `capabilities::Observe`.
+fn get_service_traits(observed: bool) -> TokenStream2 {
+    let (observe, scripted, bounds) = match observed {
+        true => (
+            quote! { ::capabilities::Observe::observe(service, &operation, required, presented); },
+            quote! {
+                if let Some(result) = ::capabilities::Observe::scripted(service, &operation) {
+                    return result;
+                }
+            },
+            quote! { O: 'static, S::Data: 'static, },
+        ),
+        false => (quote! {}, quote! {}, quote! {}),
+    };
+    quote! {
+        #[doc(hidden)]
+        pub mod __capabilities {
+            use super::{CapToEnum, CapabilityTrait};
+            use ::capabilities::{CapServiceError, Capability};
+
+            /// Only `guard` makes one, so `CapabilityTrait::perform` can't be called around it.
+            pub struct Guard(::capabilities::Authorized);
+
+            impl Guard {
+                /// For the backends performing the operation themselves, see `ChannelService`.
+                pub fn authorized(self) -> ::capabilities::Authorized {
+                    self.0
+                }
+            }
+
+            /// Performs `operation` when `presented` is the capability it requires.
+            pub async fn guard<S, O>(
+                service: &S,
+                operation: O,
+                presented: Capability,
+            ) -> Result<S::Data, CapServiceError>
+            where
+                S: CapabilityTrait<O, Error = CapServiceError> + ::capabilities::Observe,
+                O: CapToEnum,
+                #bounds
+            {
+                let required = operation.into_enum();
+                #observe
+                let authorized = ::capabilities::Authorized::check(required, presented)?;
+                #scripted
+                service.perform(operation, Guard(authorized)).await
+            }
+        }
+
+        #[async_trait]
+        pub trait CapabilityTrait<Operation> {
+            type Data;
+            type Error;
+            async fn perform(
+                &self,
+                _: Operation,
+                _: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error>;
+        }
+        pub trait CapToEnum {
+            fn into_enum(&self) -> Capability;
+        }
+    }
+}
+
+/// `close`, `health` and the readiness report of a `CapService` with a single backend.
+fn get_lifecycle_code(field_id: &Ident) -> TokenStream2 {
+    let name = field_id.to_string();
+    quote! {
+        impl CapService {
+            pub async fn close(&self) {
+                ::capabilities::ServiceBackend::close(&self.#field_id).await
+            }
+
+            pub async fn health(&self) -> Result<(), CapServiceError> {
+                ::capabilities::ServiceBackend::health(&self.#field_id)
+                    .await
+                    .map_err(CapServiceError::from_backend)
+            }
+        }
+
+        #[async_trait]
+        impl ::capabilities::Readiness for CapService {
+            async fn backends(&self) -> Vec<::capabilities::BackendHealth> {
+                vec![::capabilities::BackendHealth::new(#name, self.health().await)]
+            }
+        }
+    }
+}
+
 pub fn parse_service_field_for_name(attr_args: &Vec<NestedMeta>) -> Option<MetaNameValue> {
     let mut id_vec = vec![];
     for i in attr_args {
@@ -54,19 +327,19 @@ pub fn parse_service_field_for_name(attr_args: &Vec<NestedMeta>) -> Option<MetaN
     }
 }
 
-pub fn parse_field_args_for_id(attr_args: &Vec<NestedMeta>) -> Option<MetaNameValue> {
+/// `id = "field"`, or `id("field", "other")` for a composite id, see `normalize_composite_id`.
+pub fn parse_field_args_for_id(attr_args: &Vec<NestedMeta>) -> Option<Meta> {
     let mut id_vec = vec![];
     for i in attr_args {
         let m = match i {
-            NestedMeta::Meta(
-                Meta::NameValue(nv)) => {
-                    let id = get_id_identifier();
-                    if nv.path.get_ident().unwrap().eq(&id) {
-                        Some(nv)
-                    } else {
-                        None
-                    }
-                },
+            NestedMeta::Meta(meta @ Meta::NameValue(_)) | NestedMeta::Meta(meta @ Meta::List(_)) => {
+                let id = get_id_identifier();
+                if meta.path().get_ident().unwrap().eq(&id) {
+                    Some(meta)
+                } else {
+                    None
+                }
+            }
             _ => None,
         };
         if let Some(val) = m {
@@ -81,176 +354,1460 @@ pub fn parse_field_args_for_id(attr_args: &Vec<NestedMeta>) -> Option<MetaNameVa
     }
 }
 
+/// syn cannot parse `id = ("tenant_id", "order_no")` as an attribute argument, so it becomes
+/// `id("tenant_id", "order_no")` before the arguments are parsed.
+pub fn normalize_composite_id(args: TokenStream) -> TokenStream {
+    let tokens: Vec<TokenTree> = TokenStream2::from(args).into_iter().collect();
+    let mut out = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        if let (TokenTree::Ident(ident), Some(TokenTree::Punct(eq)), Some(TokenTree::Group(group))) =
+            (&tokens[i], tokens.get(i + 1), tokens.get(i + 2))
+        {
+            if ident == "id" && eq.as_char() == '=' && group.delimiter() == Delimiter::Parenthesis {
+                out.push(tokens[i].clone());
+                out.push(tokens[i + 2].clone());
+                i += 3;
+                continue;
+            }
+        }
+        out.push(tokens[i].clone());
+        i += 1;
+    }
+    TokenStream2::from_iter(out).into()
+}
+
+/// syn cannot parse types as attribute arguments either, so the types of a custom verb like
+/// `Approve(input = ApprovalNote, output = Invoice)` become string literals.
+pub fn normalize_verb_types(args: TokenStream) -> TokenStream {
+    let tokens: Vec<TokenTree> = TokenStream2::from(args).into_iter().collect();
+    let mut out = vec![];
+    for (i, token) in tokens.iter().enumerate() {
+        let verb = i.checked_sub(1).map(|previous| &tokens[previous]);
+        match (token, verb) {
+            (TokenTree::Group(group), Some(TokenTree::Ident(verb)))
+                if verb != "id" && group.delimiter() == Delimiter::Parenthesis =>
+            {
+                let mut normalized =
+                    Group::new(Delimiter::Parenthesis, stringify_verb_types(group.stream()));
+                normalized.set_span(group.span());
+                out.push(TokenTree::Group(normalized));
+            }
+            _ => out.push(token.clone()),
+        }
+    }
+    TokenStream2::from_iter(out).into()
+}
+
+fn stringify_verb_types(stream: TokenStream2) -> TokenStream2 {
+    let tokens: Vec<TokenTree> = stream.into_iter().collect();
+    let mut out = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let is_type = match (&tokens[i], tokens.get(i + 1)) {
+            (TokenTree::Ident(name), Some(TokenTree::Punct(eq))) => {
+                (name == VERB_INPUT || name == VERB_OUTPUT) && eq.as_char() == '='
+            }
+            _ => false,
+        };
+        if !is_type {
+            out.push(tokens[i].clone());
+            i += 1;
+            continue;
+        }
+        out.extend_from_slice(&tokens[i..i + 2]);
+        i += 2;
+
+        // The type runs up to the next comma outside of its generics.
+        let start = i;
+        let mut depth = 0;
+        while i < tokens.len() {
+            if let TokenTree::Punct(p) = &tokens[i] {
+                match p.as_char() {
+                    '<' => depth += 1,
+                    '>' => depth -= 1,
+                    ',' if depth == 0 => break,
+                    _ => {}
+                }
+            }
+            i += 1;
+        }
+        match &tokens[start..i] {
+            [literal @ TokenTree::Literal(_)] => out.push(literal.clone()),
+            ty => {
+                let mut literal = Literal::string(&TokenStream2::from_iter(ty.to_vec()).to_string());
+                if let Some(first) = ty.first() {
+                    literal.set_span(first.span());
+                }
+                out.push(TokenTree::Literal(literal));
+            }
+        }
+    }
+    TokenStream2::from_iter(out)
+}
+
+/// Takes `route = "primary"` or `route = "replica"` out of the `#[capability]` arguments.
+pub fn parse_capability_route(attr_args: &mut Vec<NestedMeta>) -> TokenStream2 {
+    let mut route = quote! { None };
+    attr_args.retain(|i| {
+        let nv = match i {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident(ROUTE) => nv,
+            _ => return true,
+        };
+        match &nv.lit {
+            Lit::Str(r) if r.value() == ROUTE_PRIMARY => {
+                route = quote! { Some(::capabilities::Route::Primary) }
+            }
+            Lit::Str(r) if r.value() == ROUTE_REPLICA => {
+                route = quote! { Some(::capabilities::Route::Replica) }
+            }
+            lit => lit
+                .span()
+                .unstable()
+                .error("route is either \"primary\" or \"replica\"")
+                .emit(),
+        }
+        false
+    });
+    route
+}
+
+/// The fields of the struct named by its `id`, tuple struct fields are named by their index.
+/// The generics are the ones of the struct when the id fields use them, or else none.
+pub struct IdFields {
+    pub fields: Vec<Member>,
+    pub types: Vec<Type>,
+    pub generics: Generics,
+}
+
+impl IdFields {
+    pub fn is_composite(&self) -> bool {
+        self.fields.len() > 1
+    }
+
+    /// `pub struct XId`, its only field is called `id` unless the id is composite. The fields of
+    /// a composite id on a tuple struct are called `_0`, `_1`, ...
+    pub fn id_struct(&self, typealias: &Ident) -> TokenStream2 {
+        let fields = self.id_struct_fields();
+        let types = &self.types;
+        let generics = &self.generics;
+        let where_clause = &generics.where_clause;
+        quote! { pub struct #typealias #generics #where_clause { #( #fields: #types ),* } }
+    }
+
+    pub fn id_struct_fields(&self) -> Vec<Ident> {
+        if !self.is_composite() {
+            return vec![get_id_identifier()];
+        }
+        self.fields
+            .iter()
+            .map(|f| match f {
+                Member::Named(ident) => ident.clone(),
+                Member::Unnamed(index) => format_ident!("_{}", index.index),
+            })
+            .collect()
+    }
+
+    /// The named id fields, the primary key of a table.
+    pub fn named_fields(&self) -> Vec<Ident> {
+        self.fields
+            .iter()
+            .filter_map(|f| match f {
+                Member::Named(ident) => Some(ident.clone()),
+                Member::Unnamed(_) => None,
+            })
+            .collect()
+    }
+
+    /// The id fields of `value` as a slice of `Display`s, `value` being the struct itself or,
+    /// with `of_id_struct`, its `XId`.
+    pub fn segments(&self, value: TokenStream2, of_id_struct: bool) -> TokenStream2 {
+        let fields: Vec<Member> = if of_id_struct {
+            self.id_struct_fields()
+                .into_iter()
+                .map(Member::Named)
+                .collect()
+        } else {
+            self.fields.clone()
+        };
+        quote! { &[ #( &#value.#fields ),* ] }
+    }
+}
+
 pub fn parse_metavalue_for_type(
-    id_metavalue: &Option<MetaNameValue>,
+    id_metavalue: &Option<Meta>,
     item_struct: &ItemStruct,
-) -> Option<Type> {
+) -> Option<IdFields> {
+    let names = match id_metavalue.as_ref()? {
+        Meta::NameValue(nv) => vec![nv.lit.clone()],
+        Meta::List(list) => list
+            .nested
+            .iter()
+            .filter_map(|n| match n {
+                NestedMeta::Lit(lit) => Some(lit.clone()),
+                n => {
+                    n.span().unstable().error("Expected the name of a field").emit();
+                    None
+                }
+            })
+            .collect(),
+        Meta::Path(_) => vec![],
+    };
+
+    let mut id = IdFields {
+        fields: vec![],
+        types: vec![],
+        generics: Generics::default(),
+    };
+    for name in names {
+        let field_name = match &name {
+            Lit::Str(a) => a.value(),
+            lit => {
+                lit.span().unstable().error("Expected the name of a field").emit();
+                continue;
+            }
+        };
+        let field = item_struct
+            .fields
+            .iter()
+            .enumerate()
+            .find(|(i, f)| match &f.ident {
+                Some(ident) => ident == &field_name,
+                None => i.to_string() == field_name,
+            });
+        match field {
+            Some((i, f)) => {
+                id.fields.push(match &f.ident {
+                    Some(ident) => Member::Named(ident.clone()),
+                    None => Member::Unnamed(Index {
+                        index: i as u32,
+                        span: name.span(),
+                    }),
+                });
+                id.types.push(f.ty.clone());
+            }
+            None => name
+                .span()
+                .unstable()
+                .error(format!("{} has no field {}", item_struct.ident, field_name))
+                .emit(),
+        }
+    }
+    if id.fields.is_empty() {
+        return None;
+    }
+
+    // `XId` can't take only some of the generics, the capability fns name it with the
+    // arguments of the struct.
+    let params = &item_struct.generics.params;
+    let used = params
+        .iter()
+        .filter(|p| id.types.iter().any(|ty| mentions(quote! { #ty }, param_ident(p))))
+        .count();
+    if used == params.len() {
+        id.generics = item_struct.generics.clone();
+    } else if used > 0 {
+        id_metavalue
+            .span()
+            .unstable()
+            .error("The id of a generic struct uses either all of its generics or none")
+            .emit();
+    }
+    Some(id)
+}
+
+fn param_ident(param: &GenericParam) -> &Ident {
+    match param {
+        GenericParam::Type(t) => &t.ident,
+        GenericParam::Lifetime(l) => &l.lifetime.ident,
+        GenericParam::Const(c) => &c.ident,
+    }
+}
+
+/// Whether `tokens` name `ident`, like the type of a field naming a generic parameter.
+fn mentions(tokens: TokenStream2, ident: &Ident) -> bool {
+    tokens.into_iter().any(|t| match t {
+        TokenTree::Ident(i) => i == *ident,
+        TokenTree::Group(g) => mentions(g.stream(), ident),
+        _ => false,
+    })
+}
+
+pub fn get_id_type(id_metavalue: &Option<Meta>, item_struct: &Ident) -> Option<Ident> {
     let out = if id_metavalue.is_none() {
-        None
+        Some(item_struct.to_owned())
     } else {
-        let mut id_type = vec![];
-        let id_field_name = match &id_metavalue.as_ref().unwrap().lit {
+        Some(format_ident!("{}Id", item_struct))
+    };
+    out
+}
+
+fn get_ident_from_field_name(field_name: Option<MetaNameValue>) -> Ident {
+    let id_field_name = if field_name.is_some() {
+        match &field_name.as_ref().unwrap().lit {
             Lit::Str(a) => Some(a.value()),
             _ => None,
+        }
+    } else {
+        None
+    };
+    let field_name = format_ident!("{}", id_field_name.unwrap_or_else( || FIELD_NAME.to_string()));
+    field_name
+}
+
+/// Turns the pool settings of `#[service(SqliteDb, max_connections = 5, ...)]`
+/// into calls on the generated `CapServiceBuilder`.
+pub fn parse_service_pool_options(attr_args: &Vec<NestedMeta>) -> Vec<TokenStream2> {
+    let mut options = vec![];
+    for i in attr_args {
+        let nv = match i {
+            NestedMeta::Meta(Meta::NameValue(nv)) => nv,
+            _ => continue,
+        };
+        let option = match nv.path.get_ident() {
+            Some(option) => option,
+            None => continue,
+        };
+        let lit = &nv.lit;
+        let setter = match (option.to_string().as_str(), lit) {
+            (POOL_MAX_CONNECTIONS, Lit::Int(_))
+            | (POOL_MIN_CONNECTIONS, Lit::Int(_))
+            | (POOL_CONNECT_RETRIES, Lit::Int(_)) => Some(quote! { .#option(#lit) }),
+            (POOL_ACQUIRE_TIMEOUT, Lit::Int(_)) | (POOL_IDLE_TIMEOUT, Lit::Int(_)) => {
+                Some(quote! { .#option(::std::time::Duration::from_secs(#lit)) })
+            }
+            (POOL_LAZY, Lit::Bool(_)) | (POOL_WAL, Lit::Bool(_)) | (POOL_FOREIGN_KEYS, Lit::Bool(_)) => {
+                Some(quote! { .#option(#lit) })
+            }
+            (POOL_MAX_CONNECTIONS, _)
+            | (POOL_MIN_CONNECTIONS, _)
+            | (POOL_CONNECT_RETRIES, _)
+            | (POOL_ACQUIRE_TIMEOUT, _)
+            | (POOL_IDLE_TIMEOUT, _) => {
+                lit.span()
+                    .unstable()
+                    .error(format!("{} takes a number, timeouts are in seconds", option))
+                    .emit();
+                None
+            }
+            (POOL_LAZY, _) | (POOL_WAL, _) | (POOL_FOREIGN_KEYS, _) => {
+                lit.span()
+                    .unstable()
+                    .error(format!("{} takes true or false", option))
+                    .emit();
+                None
+            }
+            _ => None,
         };
-        if id_field_name.is_some() {
-            let ident_fieldname = format_ident!("{}", &id_field_name.unwrap());
-            for f in &item_struct.fields {
-                let ident = f.ident.as_ref().unwrap();
-                if ident.eq(&ident_fieldname) {
-                    id_type.push(f.to_owned().ty);
+        if let Some(setter) = setter {
+            options.push(setter);
+        }
+    }
+    options
+}
+
+pub fn parse_service_migrations(attr_args: &Vec<NestedMeta>) -> Option<LitStr> {
+    let mut migrations = None;
+    for i in attr_args {
+        if let NestedMeta::Meta(Meta::NameValue(nv)) = i {
+            if !nv.path.is_ident(MIGRATIONS) {
+                continue;
+            }
+            match &nv.lit {
+                Lit::Str(path) => migrations = Some(path.to_owned()),
+                lit => lit
+                    .span()
+                    .unstable()
+                    .error("migrations takes the path to the migrations directory")
+                    .emit(),
+            }
+        }
+    }
+    migrations
+}
+
+fn get_migrations_code(field_id: &Ident, migrations: Option<LitStr>) -> (TokenStream2, TokenStream2) {
+    match migrations {
+        Some(path) => (
+            quote! {
+                static CAP_MIGRATOR: ::sqlx::migrate::Migrator = ::sqlx::migrate!(#path);
+
+                impl CapService {
+                    pub async fn migrate(&self) -> Result<(), CapServiceError> {
+                        CAP_MIGRATOR.run(&self.#field_id).await?;
+                        Ok(())
+                    }
                 }
+            },
+            quote! { service.migrate().await?; },
+        ),
+        None => (quote! {}, quote! {}),
+    }
+}
+
+pub fn impl_code_database(
+    service_token: &Meta,
+    item: Item,
+    field_name: Option<MetaNameValue>,
+    pool_options: Vec<TokenStream2>,
+    migrations: Option<LitStr>,
+) -> TokenStream {
+    let field_id = get_ident_from_field_name(field_name);
+    let service_traits = get_service_traits(false);
+    let (migrator, run_migrations) = get_migrations_code(&field_id, migrations);
+    let name = field_id.to_string();
+    let replica_name = format!("{}_replica", field_id);
+
+    let out = quote! {
+        use sqlx::Pool;
+        use async_trait::async_trait;
+        use ::capabilities::Capability;
+        use ::capabilities::CapServiceError;
+
+        type CapDatabase = <#service_token as ::capabilities::PoolDatabase>::Database;
+
+        #[derive(Clone)]
+        pub struct CapService {
+            #field_id: #service_token,
+            replica: Option<#service_token>,
+            tx: ::capabilities::TxSlot<CapDatabase>,
+        }
+
+        pub type CapServiceBuilder = ::capabilities::PoolBuilder<CapService>;
+
+        impl CapService {
+            pub fn builder() -> CapServiceBuilder {
+                ::capabilities::PoolBuilder::new() #( #pool_options )*
             }
-            let val = id_type.pop().unwrap();
-            Some(val)
-        } else {
-            None
+
+            /// A connection of the transaction the service runs in, or of the pool.
+            pub async fn connection(
+                &self,
+            ) -> Result<::capabilities::DbConnection<'_, CapDatabase>, CapServiceError> {
+                self.tx.connection(&self.#field_id).await
+            }
+
+            pub fn in_transaction(&self) -> bool {
+                self.tx.is_active()
+            }
+
+            /// Runs `f` with a service bound to a new transaction, committed when `f` returns `Ok`
+            /// and rolled back otherwise. Within a transaction `f` joins the running one.
+            ///
+            /// Queries of `f` belong to the transaction when they run on `connection()`, the pool
+            /// field of the service still runs them outside of it and can wait for the connection
+            /// the transaction holds, see `capabilities::TxSlot`.
+            pub async fn transaction<F, Fut, T>(&self, f: F) -> Result<T, CapServiceError>
+            where
+                F: FnOnce(CapService) -> Fut,
+                Fut: ::std::future::Future<Output = Result<T, CapServiceError>>,
+            {
+                if self.tx.is_active() {
+                    return f(self.clone()).await;
+                }
+                let tx = ::capabilities::TxSlot::begin(&self.#field_id).await?;
+                let service = CapService {
+                    #field_id: self.#field_id.clone(),
+                    replica: self.replica.clone(),
+                    tx: tx.clone(),
+                };
+                let result = f(service).await;
+                tx.finish(result.is_ok()).await?;
+                result
+            }
+
+            pub async fn build(conf: String) -> Result<Self, CapServiceError> {
+                Self::builder().url(conf).build().await
+            }
+
+            /// `Read` and `ReadAll` are performed on `replica`, everything else on `primary`.
+            pub async fn build_with_replica(
+                primary: String,
+                replica: String,
+            ) -> Result<Self, CapServiceError> {
+                Self::builder().url(primary).replica_url(replica).build().await
+            }
+
+            pub async fn build_from_env() -> Result<Self, CapServiceError> {
+                let config = ::capabilities::PoolConfig::from_env()?;
+                let builder = Self::builder().url(config.url);
+                match config.replica_url {
+                    Some(replica) => builder.replica_url(replica).build().await,
+                    None => builder.build().await,
+                }
+            }
+        }
+
+        impl CapService {
+            /// Closes the pools, waiting for the connections in use to be returned.
+            pub async fn close(&self) {
+                ::capabilities::ServiceBackend::close(&self.#field_id).await;
+                if let Some(replica) = &self.replica {
+                    ::capabilities::ServiceBackend::close(replica).await;
+                }
+            }
+
+            pub async fn health(&self) -> Result<(), CapServiceError> {
+                ::capabilities::ServiceBackend::health(&self.#field_id).await?;
+                if let Some(replica) = &self.replica {
+                    ::capabilities::ServiceBackend::health(replica).await?;
+                }
+                Ok(())
+            }
+        }
+
+        #[async_trait]
+        impl ::capabilities::Readiness for CapService {
+            async fn backends(&self) -> Vec<::capabilities::BackendHealth> {
+                let primary = ::capabilities::ServiceBackend::health(&self.#field_id).await;
+                let mut backends = vec![::capabilities::BackendHealth::new(
+                    #name,
+                    primary.map_err(CapServiceError::from),
+                )];
+                if let Some(replica) = &self.replica {
+                    let health = ::capabilities::ServiceBackend::health(replica).await;
+                    backends.push(::capabilities::BackendHealth::new(
+                        #replica_name,
+                        health.map_err(CapServiceError::from),
+                    ));
+                }
+                backends
+            }
+        }
+
+        impl ::capabilities::Routing for CapService {
+            fn route(
+                &self,
+                required: Capability,
+                pinned: Option<::capabilities::Route>,
+            ) -> ::std::borrow::Cow<'_, Self> {
+                // A transaction only sees its own writes on the primary.
+                match (&self.replica, ::capabilities::Route::select(required, pinned)) {
+                    (Some(replica), ::capabilities::Route::Replica) if !self.tx.is_active() => {
+                        ::std::borrow::Cow::Owned(CapService {
+                            #field_id: replica.clone(),
+                            replica: None,
+                            tx: ::capabilities::TxSlot::default(),
+                        })
+                    }
+                    _ => ::std::borrow::Cow::Borrowed(self),
+                }
+            }
+        }
+
+        #[async_trait]
+        impl ::capabilities::FromPoolConfig for CapService {
+            async fn from_pool_config(
+                config: ::capabilities::PoolConfig,
+            ) -> Result<Self, CapServiceError> {
+                let replica = match config.replica() {
+                    Some(replica) => {
+                        Some(<#service_token as ::capabilities::ServiceBackend>::build(replica).await?)
+                    }
+                    None => None,
+                };
+                let con = <#service_token as ::capabilities::ServiceBackend>::build(config).await?;
+                let service = Self {
+                    #field_id: con,
+                    replica,
+                    tx: ::capabilities::TxSlot::default(),
+                };
+                #run_migrations
+
+                Ok(service)
+            }
+        }
+        #migrator
+        impl ::capabilities::Observe for CapService {}
+        #service_traits
+        #item
+    };
+    out.into()
+}
+
+/// Turns `#[service(WebService, base_url = "...", timeout = 10, ...)]` into
+/// calls on the generated `CapService::config()`.
+pub fn parse_service_web_options(attr_args: &Vec<NestedMeta>) -> Vec<TokenStream2> {
+    let mut options = vec![];
+    for i in attr_args {
+        let nv = match i {
+            NestedMeta::Meta(Meta::NameValue(nv)) => nv,
+            _ => continue,
+        };
+        let option = match nv.path.get_ident() {
+            Some(option) => option,
+            None => continue,
+        };
+        let lit = &nv.lit;
+        let setter = match (option.to_string().as_str(), lit) {
+            (WEB_BASE_URL, Lit::Str(_)) | (WEB_USER_AGENT, Lit::Str(_)) => {
+                Some(quote! { .#option(#lit) })
+            }
+            (WEB_TIMEOUT, Lit::Int(_)) | (WEB_CONNECT_TIMEOUT, Lit::Int(_)) => {
+                Some(quote! { .#option(::std::time::Duration::from_secs(#lit)) })
+            }
+            (WEB_ACCEPT_INVALID_CERTS, Lit::Bool(_)) => Some(quote! { .#option(#lit) }),
+            // Tokens do not belong in the source, so only the variable holding it is named here.
+            // `build` fails when it is not set.
+            (WEB_TOKEN_ENV, Lit::Str(_)) => Some(quote! { .#option(#lit) }),
+            (WEB_HEADER, Lit::Str(header)) => match header.value().split_once(':') {
+                Some((name, value)) => {
+                    let name = name.trim();
+                    let value = value.trim();
+                    Some(quote! { .header(#name, #value) })
+                }
+                None => {
+                    lit.span()
+                        .unstable()
+                        .error("header has to be written as \"Name: value\"")
+                        .emit();
+                    None
+                }
+            },
+            (WEB_BASE_URL, _) | (WEB_USER_AGENT, _) | (WEB_HEADER, _) | (WEB_TOKEN_ENV, _) => {
+                lit.span()
+                    .unstable()
+                    .error(format!("{} takes a string", option))
+                    .emit();
+                None
+            }
+            (WEB_TIMEOUT, _) | (WEB_CONNECT_TIMEOUT, _) => {
+                lit.span()
+                    .unstable()
+                    .error(format!("{} takes a number of seconds", option))
+                    .emit();
+                None
+            }
+            (WEB_ACCEPT_INVALID_CERTS, _) => {
+                lit.span()
+                    .unstable()
+                    .error(format!("{} takes true or false", option))
+                    .emit();
+                None
+            }
+            _ => None,
+        };
+        if let Some(setter) = setter {
+            options.push(setter);
+        }
+    }
+    options
+}
+
+pub fn impl_code_webservice(
+    service_token: &Meta,
+    item: Item,
+    field_name: Option<MetaNameValue>,
+    web_options: Vec<TokenStream2>,
+) -> TokenStream {
+    let field_id = get_ident_from_field_name(field_name);
+    let service_traits = get_service_traits(false);
+    let lifecycle = get_lifecycle_code(&field_id);
+
+    let out = quote! {
+        use async_trait::async_trait;
+        use ::capabilities::Capability;
+        use ::capabilities::CapServiceError;
+
+        #[derive(Clone)]
+        pub struct CapService {
+            #field_id: #service_token,
+        }
+
+        impl CapService {
+            /// The configuration given in `#[service(WebService, ...)]`.
+            pub fn config() -> ::capabilities::WebServiceConfig {
+                ::capabilities::WebServiceConfig::new() #( #web_options )*
+            }
+
+            pub async fn build() -> Result<Self, CapServiceError> {
+                Self::build_with(Self::config()).await
+            }
+
+            pub async fn build_with(
+                config: ::capabilities::WebServiceConfig,
+            ) -> Result<Self, CapServiceError> {
+                let con = <#service_token as ::capabilities::ServiceBackend>::build(config).await?;
+
+                Ok(Self { #field_id: con })
+            }
+
+            /// Joins a path onto the configured `base_url`.
+            pub fn url(&self, path: &str) -> Result<::capabilities::Url, CapServiceError> {
+                self.#field_id.url(path)
+            }
+
+            /// The same service, but its requests carry the bearer from `token`.
+            pub fn with_token(&self, token: ::capabilities::TokenSource) -> Self {
+                Self { #field_id: self.#field_id.with_token(token) }
+            }
+        }
+        impl ::capabilities::Observe for CapService {}
+        impl ::capabilities::Routing for CapService {}
+        #lifecycle
+        #service_traits
+        #item
+    };
+
+    out.into()
+}
+
+pub fn impl_code_backend(
+    service_token: &Meta,
+    item: Item,
+    field_name: Option<MetaNameValue>,
+) -> TokenStream {
+    let field_id = get_ident_from_field_name(field_name);
+    let service_traits = get_service_traits(false);
+    let lifecycle = get_lifecycle_code(&field_id);
+
+    let out = quote! {
+        use async_trait::async_trait;
+        use ::capabilities::Capability;
+        use ::capabilities::CapServiceError;
+
+        #[derive(Clone)]
+        pub struct CapService {
+            #field_id: #service_token,
+        }
+
+        impl CapService {
+            pub async fn build(
+                conf: <#service_token as ::capabilities::ServiceBackend>::Config,
+            ) -> Result<Self, CapServiceError> {
+                let con = <#service_token as ::capabilities::ServiceBackend>::build(conf)
+                    .await
+                    .map_err(CapServiceError::from_backend)?;
+
+                Ok(Self { #field_id: con })
+            }
+        }
+        #lifecycle
+        impl ::capabilities::Observe for CapService {}
+        impl ::capabilities::Routing for CapService {}
+        #service_traits
+        #item
+    };
+
+    out.into()
+}
+
+pub fn impl_code_mock(
+    service_token: &Meta,
+    item: Item,
+    field_name: Option<MetaNameValue>,
+) -> TokenStream {
+    let field_id = get_ident_from_field_name(field_name);
+    let service_traits = get_service_traits(true);
+    let lifecycle = get_lifecycle_code(&field_id);
+
+    let out = quote! {
+        use async_trait::async_trait;
+        use ::capabilities::Capability;
+        use ::capabilities::CapServiceError;
+
+        #[derive(Clone)]
+        pub struct CapService {
+            #field_id: #service_token,
         }
-    };
-    out
-}
 
-#[allow(dead_code)]
-pub fn parse_metavalue_for_type_ident(
-    id_metavalue: &Option<MetaNameValue>,
-    item_struct: &Ident,
-) -> Option<Ident> {
-    let out = if id_metavalue.is_none() {
-        Some(item_struct.to_owned())
-    } else {
-        let id_field_name = match &id_metavalue.as_ref().unwrap().lit {
-            Lit::Str(a) => Some(a.value()),
-            _ => None,
-        };
-        if id_field_name.is_some() {
-            let ident_fieldname = format_ident!("{}", &id_field_name.unwrap());
-            Some(ident_fieldname)
-        } else {
-            None
+        impl CapService {
+            pub async fn build() -> Result<Self, CapServiceError> {
+                Ok(Self { #field_id: #service_token::new() })
+            }
+
+            /// The recorded invocations and scripted results of this service.
+            pub fn mock(&self) -> &#service_token {
+                &self.#field_id
+            }
         }
-    };
-    out
-}
 
-pub fn get_id_type(id_metavalue: &Option<MetaNameValue>, item_struct: &Ident) -> Option<Ident> {
-    let out = if id_metavalue.is_none() {
-        Some(item_struct.to_owned())
-    } else {
-        Some(format_ident!("{}Id", item_struct))
-    };
-    out
-}
+        impl ::capabilities::Observe for CapService {
+            fn observe<Operation: 'static>(
+                &self,
+                operation: &Operation,
+                required: Capability,
+                presented: Capability,
+            ) {
+                ::capabilities::Observe::observe(&self.#field_id, operation, required, presented)
+            }
 
-fn get_ident_from_field_name(field_name: Option<MetaNameValue>) -> Ident {
-    let id_field_name = if field_name.is_some() {
-        match &field_name.as_ref().unwrap().lit {
-            Lit::Str(a) => Some(a.value()),
-            _ => None,
+            fn performing<Operation: 'static>(&self, operation: &Operation, required: Capability) {
+                ::capabilities::Observe::performing(&self.#field_id, operation, required)
+            }
+
+            fn scripted<Operation: 'static, Data: 'static>(
+                &self,
+                operation: &Operation,
+            ) -> Option<Result<Data, CapServiceError>> {
+                ::capabilities::Observe::scripted(&self.#field_id, operation)
+            }
         }
-    } else {
-        None
+        impl ::capabilities::Routing for CapService {}
+        #lifecycle
+        #service_traits
+        #item
     };
-    let field_name = format_ident!("{}", id_field_name.unwrap_or_else( || FIELD_NAME.to_string()));
-    field_name
+
+    out.into()
 }
 
-pub fn impl_code_database(
+pub fn impl_code_channel(
     service_token: &Meta,
     item: Item,
     field_name: Option<MetaNameValue>,
 ) -> TokenStream {
     let field_id = get_ident_from_field_name(field_name);
+    let service_traits = get_service_traits(false);
+    let lifecycle = get_lifecycle_code(&field_id);
 
     let out = quote! {
-        use sqlx::Pool;
         use async_trait::async_trait;
         use ::capabilities::Capability;
+        use ::capabilities::CapServiceError;
 
         #[derive(Clone)]
         pub struct CapService {
             #field_id: #service_token,
         }
 
-        #[derive(Debug)]
-        pub struct CapServiceError;
-
         impl CapService {
-            pub async fn build(conf: String) -> Result<Self, crate::CapServiceError> {
-                let con = Pool::connect(&conf)
-                    .await
-                    .expect("Failed to connect database");
+            /// Every channel holds up to `capacity` messages.
+            pub async fn build(capacity: usize) -> Result<Self, CapServiceError> {
+                let con = <#service_token as ::capabilities::ServiceBackend>::build(capacity).await?;
 
-                Ok ( Self { #field_id: con })
+                Ok(Self { #field_id: con })
             }
         }
+
+        impl ::capabilities::Observe for CapService {}
+        impl ::capabilities::Routing for CapService {}
+
         #[async_trait]
-        pub trait CapabilityTrait<Operation> {
-            type Data;
-            type Error;
-            async fn perform(&self, _: Operation) -> Result<Self::Data, Self::Error>;
+        impl<T> CapabilityTrait<::capabilities::Publish<T>> for CapService
+        where
+            T: Send + 'static,
+        {
+            type Data = ();
+            type Error = CapServiceError;
+
+            async fn perform(
+                &self,
+                action: ::capabilities::Publish<T>,
+                guard: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error> {
+                ::capabilities::Observe::performing(self, &action, Capability::Publish);
+                self.#field_id.publish(action, guard.authorized()).await
+            }
         }
-        pub trait CapToEnum {
-            fn into_enum(&self) -> Capability;
+
+        #[async_trait]
+        impl<T> CapabilityTrait<::capabilities::Consume<::std::marker::PhantomData<T>>> for CapService
+        where
+            T: Send + 'static,
+        {
+            type Data = T;
+            type Error = CapServiceError;
+
+            async fn perform(
+                &self,
+                action: ::capabilities::Consume<::std::marker::PhantomData<T>>,
+                guard: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error> {
+                ::capabilities::Observe::performing(self, &action, Capability::Consume);
+                self.#field_id.consume(action, guard.authorized()).await
+            }
         }
+        #lifecycle
+        #service_traits
         #item
     };
+
     out.into()
 }
 
-pub fn impl_code_webservice(
+/// `FileStore` comes with the operations of every `#[capabilities(..., collection = "name")]`
+/// struct, so there are no `#[capability]` functions. `guarded_perform` checks the capability
+/// and projects what it hands out with the grant, like the guard of a `Read` does.
+pub fn impl_code_filestore(
     service_token: &Meta,
     item: Item,
     field_name: Option<MetaNameValue>,
 ) -> TokenStream {
     let field_id = get_ident_from_field_name(field_name);
+    let service_traits = get_service_traits(false);
+    let lifecycle = get_lifecycle_code(&field_id);
 
     let out = quote! {
         use async_trait::async_trait;
         use ::capabilities::Capability;
+        use ::capabilities::CapServiceError;
 
         #[derive(Clone)]
         pub struct CapService {
             #field_id: #service_token,
         }
 
-        #[derive(Debug)]
-        pub struct CapServiceError;
-
         impl CapService {
-            pub async fn build() -> Result<Self, crate::CapServiceError> {
-                let con = Client::new();
+            pub async fn build(root: impl Into<::std::path::PathBuf>) -> Result<Self, CapServiceError> {
+                let con = <#service_token as ::capabilities::ServiceBackend>::build(root.into()).await?;
+
+                Ok(Self { #field_id: con })
+            }
 
-                Ok(Self { con: con })
+            /// Performs `operation`, like `Read<OrdersId>`, when the capability of `grant` grants
+            /// it. Documents are handed out as their `capabilities::Projection` for the grant.
+            pub async fn guarded_perform<Operation>(
+                &self,
+                operation: Operation,
+                grant: impl Into<::capabilities::Grant>,
+            ) -> Result<
+                <<Self as CapabilityTrait<Operation>>::Data as ::capabilities::Projection>::View,
+                CapServiceError,
+            >
+            where
+                Self: CapabilityTrait<Operation, Error = CapServiceError>,
+                <Self as CapabilityTrait<Operation>>::Data: ::capabilities::Projection,
+                Operation: CapToEnum + Send + 'static,
+            {
+                let grant: ::capabilities::Grant = grant.into();
+                let output = __capabilities::guard(self, operation, grant.capability).await?;
+                Ok(::capabilities::Projection::project(output, &grant))
             }
         }
+
+        impl ::capabilities::Observe for CapService {}
+        impl ::capabilities::Routing for CapService {}
+
         #[async_trait]
-        pub trait CapabilityTrait<Operation> {
-            type Data;
-            type Error;
-            async fn perform(&self, _: Operation) -> Result<Self::Data, Self::Error>;
+        impl<T> CapabilityTrait<::capabilities::Create<T>> for CapService
+        where
+            T: ::capabilities::Document,
+        {
+            type Data = T;
+            type Error = CapServiceError;
+
+            async fn perform(
+                &self,
+                action: ::capabilities::Create<T>,
+                _guard: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error> {
+                ::capabilities::Observe::performing(self, &action, Capability::Create);
+                self.#field_id.create(action.data).await
+            }
         }
-        pub trait CapToEnum {
-            fn into_enum(&self) -> Capability;
+
+        #[async_trait]
+        impl<L> CapabilityTrait<::capabilities::Read<L>> for CapService
+        where
+            L: ::capabilities::Locator + Send + Sync + 'static,
+        {
+            type Data = L::Document;
+            type Error = CapServiceError;
+
+            async fn perform(
+                &self,
+                action: ::capabilities::Read<L>,
+                _guard: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error> {
+                ::capabilities::Observe::performing(self, &action, Capability::Read);
+                self.#field_id.read(&action.data).await
+            }
+        }
+
+        #[async_trait]
+        impl<L> CapabilityTrait<::capabilities::Update<L>> for CapService
+        where
+            L: ::capabilities::Locator + Send + Sync + 'static,
+        {
+            type Data = ();
+            type Error = CapServiceError;
+
+            async fn perform(
+                &self,
+                action: ::capabilities::Update<L>,
+                _guard: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error> {
+                ::capabilities::Observe::performing(self, &action, Capability::Update);
+                self.#field_id.update(&action.data).await
+            }
+        }
+
+        #[async_trait]
+        impl<L> CapabilityTrait<::capabilities::Delete<L>> for CapService
+        where
+            L: ::capabilities::Locator + Send + Sync + 'static,
+        {
+            type Data = ();
+            type Error = CapServiceError;
+
+            async fn perform(
+                &self,
+                action: ::capabilities::Delete<L>,
+                _guard: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error> {
+                ::capabilities::Observe::performing(self, &action, Capability::Delete);
+                self.#field_id.delete(&action.data).await
+            }
+        }
+
+        #[async_trait]
+        impl<T> CapabilityTrait<::capabilities::CreateAll<Vec<T>>> for CapService
+        where
+            T: ::capabilities::Document,
+        {
+            type Data = Vec<T>;
+            type Error = CapServiceError;
+
+            async fn perform(
+                &self,
+                action: ::capabilities::CreateAll<Vec<T>>,
+                _guard: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error> {
+                ::capabilities::Observe::performing(self, &action, Capability::CreateAll);
+                self.#field_id.create_all(action.data).await
+            }
+        }
+
+        #[async_trait]
+        impl<T> CapabilityTrait<::capabilities::ReadAll<Vec<T>>> for CapService
+        where
+            T: ::capabilities::Document,
+        {
+            type Data = Vec<T>;
+            type Error = CapServiceError;
+
+            async fn perform(
+                &self,
+                action: ::capabilities::ReadAll<Vec<T>>,
+                _guard: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error> {
+                ::capabilities::Observe::performing(self, &action, Capability::ReadAll);
+                self.#field_id.read_all().await
+            }
+        }
+
+        #[async_trait]
+        impl<F, S> CapabilityTrait<::capabilities::ReadAll<::capabilities::Query<F, S>>> for CapService
+        where
+            F: ::capabilities::QueryFilter + Send + Sync + 'static,
+            F::Item: ::capabilities::Document,
+            S: ::capabilities::QueryField<Item = F::Item> + Send + Sync + 'static,
+        {
+            type Data = ::capabilities::Page<F::Item>;
+            type Error = CapServiceError;
+
+            async fn perform(
+                &self,
+                action: ::capabilities::ReadAll<::capabilities::Query<F, S>>,
+                _guard: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error> {
+                ::capabilities::Observe::performing(self, &action, Capability::ReadAll);
+                action.data.apply(self.#field_id.read_all().await?)
+            }
+        }
+
+        #[async_trait]
+        impl<T> CapabilityTrait<::capabilities::UpdateAll<Vec<T>>> for CapService
+        where
+            T: ::capabilities::Document,
+        {
+            type Data = ();
+            type Error = CapServiceError;
+
+            async fn perform(
+                &self,
+                action: ::capabilities::UpdateAll<Vec<T>>,
+                _guard: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error> {
+                ::capabilities::Observe::performing(self, &action, Capability::UpdateAll);
+                self.#field_id.update_all(&action.data).await
+            }
         }
+
+        #[async_trait]
+        impl<T> CapabilityTrait<::capabilities::DeleteAll<Vec<T>>> for CapService
+        where
+            T: ::capabilities::Document,
+        {
+            type Data = ();
+            type Error = CapServiceError;
+
+            async fn perform(
+                &self,
+                action: ::capabilities::DeleteAll<Vec<T>>,
+                _guard: __capabilities::Guard,
+            ) -> Result<Self::Data, Self::Error> {
+                ::capabilities::Observe::performing(self, &action, Capability::DeleteAll);
+                self.#field_id.delete_all(&action.data).await
+            }
+        }
+        #lifecycle
+        #service_traits
         #item
     };
 
     out.into()
 }
 
+/// `InvoiceEvent` becomes `invoice_event`, for the names of generated functions.
+pub fn get_snake_case(ident: &Ident) -> String {
+    let mut name = String::new();
+    for (i, c) in ident.to_string().chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                name.push('_');
+            }
+            name.extend(c.to_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+/// `Person` and `"email"` give `PersonByEmail`.
+pub fn get_key_struct(struct_name: &Ident, field: &str) -> Ident {
+    let mut name = String::new();
+    for part in field.split('_') {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.extend(first.to_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    format_ident!("{}By{}", struct_name, name)
+}
+
+/// A unique field named by `key = "field"`, rows can be looked up by it besides the id.
+pub struct KeyField {
+    pub field: Ident,
+    pub ty: Type,
+}
+
+pub fn parse_field_args_for_keys(
+    attr_args: &Vec<NestedMeta>,
+    item_struct: &ItemStruct,
+) -> Vec<KeyField> {
+    let mut keys = vec![];
+    for i in attr_args {
+        let nv = match i {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident(KEY) => nv,
+            _ => continue,
+        };
+        let name = match &nv.lit {
+            Lit::Str(name) => name.value(),
+            lit => {
+                lit.span().unstable().error("key takes the name of a field").emit();
+                continue;
+            }
+        };
+        let field = item_struct
+            .fields
+            .iter()
+            .find(|f| f.ident.as_ref().is_some_and(|i| i == &name));
+        match field {
+            Some(f) => keys.push(KeyField {
+                field: f.ident.clone().unwrap(),
+                ty: f.ty.clone(),
+            }),
+            None => nv
+                .lit
+                .span()
+                .unstable()
+                .error(format!("{} has no field {}", item_struct.ident, name))
+                .emit(),
+        }
+    }
+    keys
+}
+
+/// `PersonByEmail` and `cap!` traits like `CapReadPersonByEmail` for the lookup keys.
+pub fn generate_key_caps(
+    caps: &[&Path],
+    keys: &[KeyField],
+    struct_name: &Ident,
+    generics: &Generics,
+) -> TokenStream2 {
+    let capmacro = get_cap_macro();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let mut tokens = vec![];
+    for key in keys {
+        let field = &key.field;
+        let ty = &key.ty;
+        let key_struct = get_key_struct(struct_name, &field.to_string());
+        tokens.push(quote! {
+            pub struct #key_struct{ #field: #ty }
+        });
+
+        for cap in caps {
+            let cap = match cap.get_ident() {
+                Some(cap) => cap,
+                None => continue,
+            };
+            let data = match cap.to_string().as_str() {
+                "Read" => quote! { #struct_name #ty_generics },
+                "Update" | "Delete" => quote! { () },
+                _ => continue,
+            };
+            let capid = format_ident!("{}{}{}", "Cap", cap, key_struct);
+            let captrait = if generics.params.is_empty() {
+                quote! {
+                    #capmacro
+                    cap!( #capid for CapService, composing { #cap<#key_struct>, #data, CapServiceError});
+                }
+            } else {
+                generic_cap(&capid, &quote! { #cap<#key_struct> }, &data, generics)
+            };
+            tokens.push(quote! {
+                #captrait
+                impl CapToEnum for #cap<#key_struct> {
+                    fn into_enum(&self) -> ::capabilities::Capability {
+                        ::capabilities::Capability::#cap
+                    }
+                }
+            });
+        }
+    }
+    quote! { #( #tokens )* }
+}
+
+/// A verb beyond CRUD, `Approve(input = ApprovalNote, output = Invoice)`. Both types default to
+/// the struct.
+pub struct CustomVerb {
+    pub verb: Ident,
+    pub input: Type,
+    pub output: Type,
+}
+
+pub fn is_builtin_cap(cap: &Ident) -> bool {
+    BUILTIN_CAPS.iter().any(|builtin| cap == builtin)
+}
+
+/// `ApproveInvoice`, the operation of the verb `Approve` on `Invoice`.
+pub fn get_verb_struct(verb: &Ident, struct_name: &Ident) -> Ident {
+    format_ident!("{}{}", verb, struct_name)
+}
+
+pub fn parse_field_args_for_verbs(
+    attr_args: &Vec<NestedMeta>,
+    struct_name: &Ident,
+) -> Vec<CustomVerb> {
+    let struct_type: Type = parse_quote!(#struct_name);
+    let mut verbs = vec![];
+    for i in attr_args {
+        let (path, nested) = match i {
+            NestedMeta::Meta(Meta::Path(p)) => (p, None),
+            NestedMeta::Meta(Meta::List(l)) if !l.path.is_ident("id") => (&l.path, Some(&l.nested)),
+            _ => continue,
+        };
+        let verb = match path.get_ident() {
+            Some(verb) if !is_builtin_cap(verb) && verb != QUERY && verb != DTO => verb.clone(),
+            _ => continue,
+        };
+        let mut custom = CustomVerb {
+            verb,
+            input: struct_type.clone(),
+            output: struct_type.clone(),
+        };
+        for n in nested.into_iter().flatten() {
+            let nv = match n {
+                NestedMeta::Meta(Meta::NameValue(nv)) => nv,
+                n => {
+                    n.span()
+                        .unstable()
+                        .error("A verb takes input = Type and output = Type")
+                        .emit();
+                    continue;
+                }
+            };
+            let ty = match &nv.lit {
+                Lit::Str(ty) => ty.parse::<Type>(),
+                lit => {
+                    lit.span().unstable().error("Expected a type").emit();
+                    continue;
+                }
+            };
+            match ty {
+                Ok(ty) if nv.path.is_ident(VERB_INPUT) => custom.input = ty,
+                Ok(ty) if nv.path.is_ident(VERB_OUTPUT) => custom.output = ty,
+                Ok(_) => nv
+                    .path
+                    .span()
+                    .unstable()
+                    .error("A verb takes input = Type and output = Type")
+                    .emit(),
+                Err(e) => nv.lit.span().unstable().error(e.to_string()).emit(),
+            }
+        }
+        verbs.push(custom);
+    }
+    verbs
+}
+
+/// The operation struct of each custom verb, its `cap!` trait and its `Capability::Custom`,
+/// named like the actions of a token.
+pub fn generate_verb_caps(verbs: &[CustomVerb], struct_name: &Ident) -> TokenStream2 {
+    let capmacro = get_cap_macro();
+    let tokens = verbs.iter().map(|v| {
+        let verb = &v.verb;
+        let input = &v.input;
+        let output = &v.output;
+        let operation = get_verb_struct(verb, struct_name);
+        let capid = format_ident!("{}{}{}", "Cap", verb, struct_name);
+        let name = verb.to_string().to_lowercase();
+        quote! {
+            pub struct #operation {
+                pub data: #input,
+            }
+
+            #capmacro
+            cap!( #capid for CapService, composing { #operation, #output, CapServiceError});
+            impl CapToEnum for #operation {
+                fn into_enum(&self) -> ::capabilities::Capability {
+                    ::capabilities::Capability::Custom(::capabilities::Verb::new(#name))
+                }
+            }
+        }
+    });
+    if verbs.is_empty() {
+        return quote! {};
+    }
+    let names = verbs.iter().map(|v| v.verb.to_string().to_lowercase());
+    let doc = format!(
+        "Lets tokens grant the verbs of `{}`, see `capabilities::register_verb`.",
+        struct_name
+    );
+    quote! {
+        #( #tokens )*
+
+        impl #struct_name {
+            #[doc = #doc]
+            pub fn register_verbs() {
+                #( ::capabilities::register_verb(#names); )*
+            }
+        }
+    }
+}
+
+/// A `cap!` trait taking the generics of the struct. It is implemented for every service
+/// performing its operation, as the trait for one `CapService` could not name the generics.
+fn generic_cap(
+    name: &Ident,
+    operation: &TokenStream2,
+    data: &TokenStream2,
+    generics: &Generics,
+) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let mut service_generics = generics.clone();
+    service_generics.params.push(parse_quote!(CapServiceImpl));
+    let (service_generics, _, _) = service_generics.split_for_impl();
+    let predicates = where_clause.map(|w| &w.predicates);
+    quote! {
+        pub trait #name #impl_generics: ::capabilities::Observe + CapabilityTrait<#operation, Data = #data, Error = CapServiceError> #where_clause {}
+
+        impl #service_generics #name #ty_generics for CapServiceImpl
+        where
+            CapServiceImpl: ::capabilities::Observe + CapabilityTrait<#operation, Data = #data, Error = CapServiceError>,
+            #predicates
+        {}
+    }
+}
+
+/// The `cap!` traits of a generic struct or enum, see `generic_cap`. Operations by id keep the
+/// generics in their data, a capability fn performing them fixes the arguments.
+pub fn generate_generic_caps(
+    caps: &[&Path],
+    id_type: Option<&IdFields>,
+    struct_name: &Ident,
+    generics: &Generics,
+) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let struct_type = quote! { #struct_name #ty_generics };
+    let idstruct = format_ident!("{}Id", struct_name);
+    // `XId` takes the generics when its fields use them, see `parse_metavalue_for_type`.
+    let (id_generics, id_arguments, id_where) = match id_type {
+        Some(id) if !id.generics.params.is_empty() => (
+            quote! { #impl_generics },
+            quote! { #ty_generics },
+            quote! { #where_clause },
+        ),
+        _ => (quote! {}, quote! {}, quote! {}),
+    };
+
+    let mut tokens = vec![];
+    for cap in caps {
+        let cap = match cap.get_ident() {
+            Some(cap) => cap,
+            None => continue,
+        };
+        let (operation, data) = match cap.to_string().as_str() {
+            "Create" | "Read" => (quote! { #cap<#struct_type> }, struct_type.clone()),
+            "Update" | "Delete" => (quote! { #cap<#struct_type> }, quote! { () }),
+            "CreateAll" | "ReadAll" => (
+                quote! { #cap<Vec<#struct_type>> },
+                quote! { Vec<#struct_type> },
+            ),
+            "UpdateAll" | "DeleteAll" => (quote! { #cap<Vec<#struct_type>> }, quote! { () }),
+            _ => {
+                cap.span()
+                    .unstable()
+                    .error(format!("{} is not supported for generic types", cap))
+                    .emit();
+                continue;
+            }
+        };
+        let capident = format_ident!("{}{}{}", "Cap", cap, struct_name);
+        let captrait = generic_cap(&capident, &operation, &data, generics);
+        tokens.push(quote! {
+            #captrait
+            impl #impl_generics CapToEnum for #operation #where_clause {
+                fn into_enum(&self) -> ::capabilities::Capability {
+                    ::capabilities::Capability::#cap
+                }
+            }
+        });
+
+        if id_type.is_some() && matches!(cap.to_string().as_str(), "Read" | "Update" | "Delete") {
+            let capid = format_ident!("{}Id", capident);
+            let operation = quote! { #cap<#idstruct #id_arguments> };
+            let captrait = generic_cap(&capid, &operation, &data, generics);
+            tokens.push(quote! {
+                #captrait
+                impl #id_generics CapToEnum for #operation #id_where {
+                    fn into_enum(&self) -> ::capabilities::Capability {
+                        ::capabilities::Capability::#cap
+                    }
+                }
+            });
+        }
+    }
+    quote! { #( #tokens )* }
+}
+
+/// With `dto`, `Create` takes `NewX` and `Update` takes `XPatch`, see `dto::impl_dto`.
 pub fn generate_caps(
     capabilities: &Vec<Ident>,
-    id_type: Option<Type>,
+    id_type: Option<&IdFields>,
     struct_name: &Ident,
+    dto: bool,
 ) -> TokenStream2 {
     let create = format_ident!("{}{}", "CapCreate", struct_name).to_string();
     let read = format_ident!("{}{}", "CapRead", struct_name).to_string();
     let update = format_ident!("{}{}", "CapUpdate", struct_name).to_string();
     let delete = format_ident!("{}{}", "CapDelete", struct_name).to_string();
+    let createall = format_ident!("{}{}", "CapCreateAll", struct_name).to_string();
     let readall = format_ident!("{}{}", "CapReadAll", struct_name).to_string();
+    let streamall = format_ident!("{}{}", "CapStreamAll", struct_name).to_string();
     let deleteall = format_ident!("{}{}", "CapDeleteAll", struct_name).to_string();
     let updateall = format_ident!("{}{}", "CapUpdateAll", struct_name).to_string();
+    let publish = format_ident!("{}{}", "CapPublish", struct_name).to_string();
+    let consume = format_ident!("{}{}", "CapConsume", struct_name).to_string();
     let idstruct = format_ident!("{}Id", struct_name);
+    let (create_input, update_input) = match dto {
+        true => (get_new_struct(struct_name), get_patch_struct(struct_name)),
+        false => (struct_name.clone(), struct_name.clone()),
+    };
 
     let mut tokens = vec![];
     let capmacro = get_cap_macro();
@@ -259,9 +1816,9 @@ pub fn generate_caps(
         let outtokens = if cap.to_string().eq(&create) {
             Some(quote! {
                 #capmacro
-                cap!( #cap for CapService, composing { Create<#struct_name>, #struct_name, CapServiceError});
+                cap!( #cap for CapService, composing { Create<#create_input>, #struct_name, CapServiceError});
 
-                impl CapToEnum for Create<#struct_name> {
+                impl CapToEnum for Create<#create_input> {
                     fn into_enum(&self) -> Capability {
                         Capability::Create
                     }
@@ -300,13 +1857,32 @@ pub fn generate_caps(
                 None
             }
         } else if cap.to_string().eq(&update) {
-            if id_type.is_some() {
+            if id_type.is_some() && dto {
+                // The patch of `dto` carries the id, so an update by id is optional next to it,
+                // the service has the trait once a `#[capability(Update, X, id = ...)]` fn exists.
+                Some(quote! {
+                    #capmacro
+                    cap!( #cap for CapService, composing { Update<#update_input>, (), CapServiceError});
+                    pub trait #capid: ::capabilities::Observe + CapabilityTrait<Update<#idstruct>, Data = (), Error = CapServiceError> {}
+                    impl<S> #capid for S where S: ::capabilities::Observe + CapabilityTrait<Update<#idstruct>, Data = (), Error = CapServiceError> {}
+                    impl CapToEnum for Update<#update_input> {
+                        fn into_enum(&self) -> ::capabilities::Capability {
+                            ::capabilities::Capability::Update
+                        }
+                    }
+                    impl CapToEnum for Update<#idstruct> {
+                        fn into_enum(&self) -> ::capabilities::Capability {
+                            ::capabilities::Capability::Update
+                        }
+                    }
+                })
+            } else if id_type.is_some() {
                 Some(quote! {
                     #capmacro
 
                     cap!( #capid for CapService, composing { Update<#idstruct>, (), CapServiceError});
-                    cap!( #cap for CapService, composing { Update<#struct_name>, (), CapServiceError});
-                    impl CapToEnum for Update<#struct_name> {
+                    cap!( #cap for CapService, composing { Update<#update_input>, (), CapServiceError});
+                    impl CapToEnum for Update<#update_input> {
                         fn into_enum(&self) -> Capability {
                             Capability::Update
                         }
@@ -318,18 +1894,16 @@ pub fn generate_caps(
                     }
 
                 })
-            } else if id_type.is_none() {
+            } else {
                 Some(quote! {
                     #capmacro
-                    cap!( #cap for CapService, composing { Update<#struct_name>, (), CapServiceError});
-                    impl CapToEnum for Update<#struct_name> {
+                    cap!( #cap for CapService, composing { Update<#update_input>, (), CapServiceError});
+                    impl CapToEnum for Update<#update_input> {
                         fn into_enum(&self) -> ::capabilities::Capability {
                             ::capabilities::Capability::Update
                         }
                     }
                 })
-            } else {
-                None
             }
         } else if cap.to_string().eq(&delete) {
             if id_type.is_some() {
@@ -374,6 +1948,16 @@ pub fn generate_caps(
                     }
                 }
             })
+        } else if cap.to_string().eq(&createall) {
+            Some(quote! {
+                #capmacro
+                cap!( #cap for CapService, composing { CreateAll<Vec<#struct_name>>, Vec<#struct_name>, CapServiceError});
+                impl CapToEnum for CreateAll<Vec<#struct_name>> {
+                    fn into_enum(&self) -> Capability {
+                        Capability::CreateAll
+                    }
+                }
+            })
         } else if cap.to_string().eq(&updateall) {
             Some(quote! {
                 #capmacro
@@ -397,6 +1981,55 @@ pub fn generate_caps(
                     }
                 }
             })
+        } else if cap.to_string().eq(&streamall) {
+            Some(quote! {
+                #capmacro
+                cap!( #cap for CapService, composing { StreamAll<#struct_name>, ::capabilities::CapStream<#struct_name>, CapServiceError});
+                impl CapToEnum for StreamAll<#struct_name> {
+                    fn into_enum(&self) -> Capability {
+                        Capability::ReadAll
+                    }
+                }
+            })
+        } else if cap.to_string().eq(&publish) {
+            let fn_signature = format_ident!("publish_{}", get_snake_case(struct_name));
+            let guard = get_guard_code(&format_ident!("Publish"));
+            Some(quote! {
+                #capmacro
+                cap!( #cap for CapService, composing { ::capabilities::Publish<#struct_name>, (), CapServiceError});
+                impl CapToEnum for ::capabilities::Publish<#struct_name> {
+                    fn into_enum(&self) -> Capability {
+                        Capability::Publish
+                    }
+                }
+
+                pub async fn #fn_signature<Service>(service: &Service, param: #struct_name, cap: ::capabilities::Capability) -> Result<(), CapServiceError>
+                where
+                    Service: #cap,
+                {
+                    #guard
+                }
+            })
+        } else if cap.to_string().eq(&consume) {
+            let fn_signature = format_ident!("consume_{}", get_snake_case(struct_name));
+            let guard = get_guard_code(&format_ident!("Consume"));
+            Some(quote! {
+                #capmacro
+                cap!( #cap for CapService, composing { ::capabilities::Consume<::std::marker::PhantomData<#struct_name>>, #struct_name, CapServiceError});
+                impl CapToEnum for ::capabilities::Consume<::std::marker::PhantomData<#struct_name>> {
+                    fn into_enum(&self) -> Capability {
+                        Capability::Consume
+                    }
+                }
+
+                pub async fn #fn_signature<Service>(service: &Service, cap: ::capabilities::Capability) -> Result<#struct_name, CapServiceError>
+                where
+                    Service: #cap,
+                {
+                    let param = ::std::marker::PhantomData;
+                    #guard
+                }
+            })
         } else {
             None
         };
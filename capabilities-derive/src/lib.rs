@@ -1,21 +1,44 @@
 #![feature(proc_macro_diagnostic)]
+mod client;
+mod document;
+mod dto;
 mod helpers;
+mod projection;
+mod query;
+mod schema;
 
+use client::{impl_remote_client, parse_field_args_for_client};
+use document::{impl_document, parse_field_args_for_collection};
+use dto::{get_dto_input, impl_dto, parse_field_args_for_dto, strip_cap_attrs};
 use helpers::{
-    generate_caps, get_id_type, impl_code_database, impl_code_webservice, parse_field_args_for_id,
-    parse_metavalue_for_type, parse_service_field_for_name,
+    generate_caps, generate_generic_caps, generate_key_caps, generate_verb_caps, get_guard_code,
+    get_guard_generics, get_id_type, get_key_struct, get_operation_guard_code, get_perform_code,
+    get_projection_guard_code, get_result_type, get_struct_arguments, get_verb_struct,
+    impl_code_backend, impl_code_channel, impl_code_database, impl_code_filestore, impl_code_mock,
+    impl_code_webservice, is_builtin_cap, normalize_composite_id, normalize_verb_types,
+    parse_capability_route, parse_field_args_for_id, parse_field_args_for_keys,
+    parse_field_args_for_verbs, parse_metavalue_for_type, parse_service_field_for_name,
+    parse_service_migrations, parse_service_pool_options, parse_service_web_options,
 };
 use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use projection::impl_projection;
+use query::{get_query_struct, impl_query, parse_field_args_for_query};
+use schema::{impl_table_schema, parse_field_args_for_table};
 
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, ToTokens};
 use syn::spanned::Spanned;
 use syn::FnArg::Typed;
-use syn::{parse_macro_input, AttributeArgs, Item, Meta, NestedMeta};
-use syn::{Block, Ident, Pat};
+use syn::{parse_macro_input, AttributeArgs, Item, Lit, Meta, NestedMeta};
+use syn::{Block, Ident, Pat, PathArguments, Signature};
 
 const POOL_SQLITE: &str = "SqliteDb";
 const POOL_POSTGRES: &str = "PostgresDb";
+const POOL_MYSQL: &str = "MySqlDb";
 const WEB_SERVICE: &str = "WebService";
+const MOCK_SERVICE: &str = "MockService";
+const FILE_STORE: &str = "FileStore";
+const CHANNEL_SERVICE: &str = "ChannelService";
 const CAP_PREFIX: &str = "Cap";
 
 /*
@@ -44,22 +67,9 @@ pub fn service(args: TokenStream, annotated_item: TokenStream) -> TokenStream {
     let service_type = match service.unwrap() {
         NestedMeta::Meta(nm) => {
             let allowed_type = match nm.clone() {
-                Meta::Path(type_ident) => {
-                    let t = match type_ident.get_ident().unwrap().to_string().as_str() {
-                        POOL_SQLITE => Some(nm),
-                        POOL_POSTGRES => Some(nm),
-                        WEB_SERVICE => Some(nm),
-                        _ => {
-                            nm.span()
-                                .unstable()
-                                .error("Only \"PoolSqlite\", \"PoolPostgres\", or \"WebService\" allowed")
-                                .emit();
-                            None
-                        }
-                    };
-
-                    t
-                }
+                // Anything else than the types we know has to implement
+                // `capabilities::ServiceBackend`, the compiler tells the user if it does not.
+                Meta::Path(_) => Some(nm),
                 _ => {
                     let ident = nm.path().get_ident().unwrap().to_string();
                     nm.span()
@@ -92,39 +102,54 @@ pub fn service(args: TokenStream, annotated_item: TokenStream) -> TokenStream {
         None
     };
     let service_field = parse_service_field_for_name(&input_args);
+    let pool_options = parse_service_pool_options(&input_args);
+    let migrations = parse_service_migrations(&input_args);
+    let web_options = parse_service_web_options(&input_args);
 
-    let out = match service_token
+    let service_name = service_token
         .as_ref()
         .unwrap()
         .path()
         .get_ident()
-        .unwrap()
-        .to_string()
-        .as_str()
-    {
+        .map(|ident| ident.to_string())
+        .unwrap_or_default();
+
+    let out = match service_name.as_str() {
         POOL_SQLITE => Some(impl_code_database(
             &service_token.unwrap(),
             item,
             service_field,
+            pool_options,
+            migrations,
         )),
         POOL_POSTGRES => Some(impl_code_database(
             &service_token.unwrap(),
             item,
             service_field,
+            pool_options,
+            migrations,
+        )),
+        POOL_MYSQL => Some(impl_code_database(
+            &service_token.unwrap(),
+            item,
+            service_field,
+            pool_options,
+            migrations,
         )),
         WEB_SERVICE => Some(impl_code_webservice(
             &service_token.unwrap(),
             item,
             service_field,
+            web_options,
+        )),
+        MOCK_SERVICE => Some(impl_code_mock(&service_token.unwrap(), item, service_field)),
+        FILE_STORE => Some(impl_code_filestore(&service_token.unwrap(), item, service_field)),
+        CHANNEL_SERVICE => Some(impl_code_channel(&service_token.unwrap(), item, service_field)),
+        _ => Some(impl_code_backend(
+            &service_token.unwrap(),
+            item,
+            service_field,
         )),
-        _ => {
-            service_token
-                .span()
-                .unstable()
-                .error("Unknown error")
-                .emit();
-            None
-        }
     };
     if out.is_none() {}
     out.unwrap()
@@ -139,23 +164,20 @@ pub fn service(args: TokenStream, annotated_item: TokenStream) -> TokenStream {
 #[proc_macro_attribute]
 pub fn capabilities(args: TokenStream, annotated_item: TokenStream) -> TokenStream {
     let item: Item = parse_macro_input!(annotated_item);
+    let args = normalize_verb_types(normalize_composite_id(args));
     let attr_args: AttributeArgs = parse_macro_input!(args);
 
-    let s = match item {
-        Item::Struct(ref s) => Some(s.to_owned()),
+    let (struct_id, generics, item_struct) = match &item {
+        Item::Struct(s) => (&s.ident, &s.generics, Some(s)),
+        Item::Enum(e) => (&e.ident, &e.generics, None),
         _ => {
             item.span()
                 .unstable()
-                .error("We only support structs")
+                .error("We only support structs and enums")
                 .emit();
-            None
+            return item.into_token_stream().into();
         }
     };
-    if s.is_none() {
-        panic!("Cannot continue");
-    }
-
-    let item_struct = s.unwrap();
 
     let mut caps = vec![];
     for t in &attr_args {
@@ -164,40 +186,193 @@ pub fn capabilities(args: TokenStream, annotated_item: TokenStream) -> TokenStre
             _ => None,
         };
         if let Some(val) = m {
-            caps.push(val)
+            if val.get_ident().is_some_and(is_builtin_cap) {
+                caps.push(val)
+            }
         }
     }
     let mut capidents = vec![];
 
     for cap in &caps {
-        let capident = format_ident!(
-            "{}{}{}",
-            CAP_PREFIX,
-            cap.get_ident().unwrap(),
-            item_struct.ident
-        );
+        let capident = format_ident!("{}{}{}", CAP_PREFIX, cap.get_ident().unwrap(), struct_id);
         capidents.push(capident);
     }
 
     let id_metavalue = parse_field_args_for_id(&attr_args);
     // this field needs to be dynamically assigned to different stuff.
 
-    let struct_id = &item_struct.ident;
-    let id_type = parse_metavalue_for_type(&id_metavalue, &item_struct);
+    // Enums have no fields to take an id or a key from.
+    let (id_type, keys) = match item_struct {
+        Some(item_struct) => (
+            parse_metavalue_for_type(&id_metavalue, item_struct),
+            parse_field_args_for_keys(&attr_args, item_struct),
+        ),
+        None => {
+            for arg in &attr_args {
+                if let NestedMeta::Meta(meta) = arg {
+                    if meta.path().is_ident("id") || meta.path().is_ident("key") {
+                        meta.span()
+                            .unstable()
+                            .error("An enum has no fields for an id or a key")
+                            .emit();
+                    }
+                }
+            }
+            (None, vec![])
+        }
+    };
+
+    // A generic struct has no single table, client, collection or query.
+    let unsupported = |span: Span, what: &str| {
+        if !generics.params.is_empty() {
+            span.unstable()
+                .error(format!("{} is not supported for generic types", what))
+                .emit();
+            return true;
+        }
+        false
+    };
+
+    let query = parse_field_args_for_query(&attr_args);
+    // A queried `ReadAll` gets its `cap!` trait along with the query.
+    if query.is_some() && generics.params.is_empty() {
+        capidents.retain(|c| *c != format!("{}ReadAll{}", CAP_PREFIX, struct_id));
+    }
+    let query_caps = match (&query, item_struct) {
+        (Some(query), _) if unsupported(query.span(), "query") => quote! {},
+        (Some(query), _) if !caps.iter().any(|c| c.is_ident("ReadAll")) => {
+            query
+                .span()
+                .unstable()
+                .error("query is the input of ReadAll, add ReadAll")
+                .emit();
+            quote! {}
+        }
+        (Some(query), Some(item_struct)) => impl_query(query, item_struct),
+        (Some(query), None) => {
+            query
+                .span()
+                .unstable()
+                .error("A query needs a struct")
+                .emit();
+            quote! {}
+        }
+        (None, _) => quote! {},
+    };
+
+    let dto = parse_field_args_for_dto(&attr_args);
+    let dto_structs = match (&dto, item_struct) {
+        (Some(dto), _) if unsupported(dto.span(), "dto") => quote! {},
+        (Some(dto), Some(item_struct)) => {
+            let id_fields: Vec<Ident> = match &id_type {
+                Some(id) => id.named_fields(),
+                None => vec![],
+            };
+            impl_dto(dto, item_struct, &id_fields)
+        }
+        (Some(dto), None) => {
+            dto.span()
+                .unstable()
+                .error("dto needs a struct")
+                .emit();
+            quote! {}
+        }
+        (None, _) => quote! {},
+    };
+
     let typealias = format_ident!("{}Id", struct_id);
-    let generated_caps = generate_caps(&capidents, id_type.clone(), struct_id);
+    let generated_caps = if generics.params.is_empty() {
+        generate_caps(&capidents, id_type.as_ref(), struct_id, dto.is_some())
+    } else {
+        generate_generic_caps(&caps, id_type.as_ref(), struct_id, generics)
+    };
+    let key_caps = generate_key_caps(&caps, &keys, struct_id, generics);
+    let verbs = parse_field_args_for_verbs(&attr_args, struct_id);
+    let verb_caps = if generics.params.is_empty() {
+        generate_verb_caps(&verbs, struct_id)
+    } else {
+        for verb in &verbs {
+            verb.verb
+                .span()
+                .unstable()
+                .error(format!("{} is not supported for generic types", verb.verb))
+                .emit();
+        }
+        quote! {}
+    };
+
+    let table_schema = match (parse_field_args_for_table(&attr_args), item_struct) {
+        (Some(table), _) if unsupported(table.span(), "table") => quote! {},
+        (Some(table), Some(item_struct)) => {
+            let primary_key: Vec<Ident> = match &id_type {
+                Some(id) => id.named_fields(),
+                None => vec![],
+            };
+            impl_table_schema(&table, item_struct, &primary_key)
+        }
+        (Some(table), None) => {
+            table
+                .span()
+                .unstable()
+                .error("A table needs a struct")
+                .emit();
+            quote! {}
+        }
+        (None, _) => quote! {},
+    };
+
+    let remote_client = match parse_field_args_for_client(&attr_args) {
+        Some(path) if !unsupported(path.span(), "client") => {
+            impl_remote_client(
+                &path,
+                struct_id,
+                &caps,
+                id_type.as_ref(),
+                query.is_some(),
+                dto.is_some(),
+            )
+        }
+        _ => quote! {},
+    };
+
+    let document = match parse_field_args_for_collection(&attr_args) {
+        Some(collection) if !unsupported(collection.span(), "collection") => {
+            impl_document(&collection, struct_id, id_type.as_ref())
+        }
+        _ => quote! {},
+    };
+
+    // Messages like the ones of a `ChannelService` are never addressed by an id.
+    let id_struct = match &id_type {
+        Some(id_type) => id_type.id_struct(&typealias),
+        None => quote! {},
+    };
+
+    let projection = impl_projection(&item);
+
+    let mut item = item.clone();
+    strip_cap_attrs(&mut item, dto.is_some());
 
     // #( use ::capabilities::#caps;)*
     quote! {
-        #item_struct
-        pub struct #typealias{ id: #id_type }
+        #item
+        #dto_structs
+        #projection
+        #id_struct
         #generated_caps
+        #key_caps
+        #verb_caps
+        #query_caps
+        #table_schema
+        #remote_client
+        #document
     }
     .into()
 }
 
 #[proc_macro_attribute]
 pub fn capability(args: TokenStream, annotated_item: TokenStream) -> TokenStream {
+    let args = normalize_composite_id(args);
     let mut attr_args: AttributeArgs = parse_macro_input!(args);
     let item: Item = parse_macro_input!(annotated_item);
 
@@ -226,6 +401,8 @@ pub fn capability(args: TokenStream, annotated_item: TokenStream) -> TokenStream
             .emit();
     }
 
+    let route = parse_capability_route(&mut attr_args);
+
     let arg_path = if attr_args.len() == 3 {
         attr_args.pop()
     } else {
@@ -235,12 +412,21 @@ pub fn capability(args: TokenStream, annotated_item: TokenStream) -> TokenStream
     let arg_struct = attr_args.pop();
     let arg_capability = attr_args.pop();
 
+    // `key = "email"` picks the lookup key instead of the id.
+    let mut arg_key = None;
     let arg_path = if let Some(arg_path) = arg_path {
         match arg_path {
-            NestedMeta::Meta(Meta::NameValue(nv)) => {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("key") => {
+                match &nv.lit {
+                    Lit::Str(key) => arg_key = Some(key.value()),
+                    lit => lit.span().unstable().error("key takes the name of a field").emit(),
+                }
+                None
+            }
+            NestedMeta::Meta(meta @ Meta::NameValue(_)) | NestedMeta::Meta(meta @ Meta::List(_)) => {
                     let field_name = format_ident!("{}", "id");
-                    if nv.path.get_ident().unwrap().eq(&field_name) {
-                        Some(nv)
+                    if meta.path().get_ident().unwrap().eq(&field_name) {
+                        Some(meta)
                     } else {
                         None
                     }
@@ -340,34 +526,116 @@ pub fn capability(args: TokenStream, annotated_item: TokenStream) -> TokenStream
         format_ident!("{}", "CapErrorIdent")
     };
 
-    let capability: Ident = if arg_path.is_none() {
+    let key_struct = arg_key.map(|key| get_key_struct(&item_struct, &key));
+
+    let capability: Ident = if let Some(key_struct) = &key_struct {
+        format_ident!("{}{}{}", CAP_PREFIX, item_cap, key_struct)
+    } else if arg_path.is_none() {
         format_ident!("{}{}{}", CAP_PREFIX, item_cap, item_struct)
     } else {
         format_ident!("{}{}{}{}", CAP_PREFIX, item_cap, item_struct, "Id")
     };
 
     // this needs to switch if it is a ReadAll.. Should be () then.. or a new EmptyInput type?
-    let action_id = get_id_type(&arg_path, &item_struct);
-
-    let out = if capability.to_string().contains("ReadAll") {
+    let action_id = match &key_struct {
+        Some(key_struct) => Some(key_struct.clone()),
+        None => get_id_type(&arg_path, &item_struct),
+    };
+    let by_key = |cap: &str| key_struct.is_some() && item_cap == cap;
+
+    // The generics of the fn are forwarded, a generic struct takes the arguments it has in the
+    // signature, e.g. `Wrapper<T>`.
+    let generics = &s.unwrap().sig.generics;
+    // A generic `XId` takes the arguments of the struct, e.g. `WrapperId<T>`.
+    let id_arguments = match &action_id {
+        Some(action_id) if action_id != &item_struct => {
+            get_struct_arguments(&s.unwrap().sig, action_id)
+        }
+        _ => PathArguments::None,
+    };
+    let struct_arguments = match get_struct_arguments(&s.unwrap().sig, &item_struct) {
+        PathArguments::None => id_arguments.clone(),
+        arguments => arguments,
+    };
+    let item_type = quote! { #item_struct #struct_arguments };
+    let capability_bound = quote! { #capability #struct_arguments };
+    // `NewX` and `XPatch` of `#[capabilities(..., dto)]` replace the struct as the input.
+    let dto_input = get_dto_input(&s.unwrap().sig, &item_struct);
+
+    let out = if !is_builtin_cap(&item_cap) {
+        let out = impl_verb_function_trait(
+            &s.unwrap().sig,
+            &item_struct,
+            item_cap,
+            fn_attrname,
+            capability_bound.clone(),
+            fn_block,
+            &route,
+        );
+        out.into()
+    } else if capability.to_string().contains("StreamAll") {
+        let out = impl_streamall_function_trait(
+            &s.unwrap().sig,
+            item_type.clone(),
+            item_cap,
+            capability_bound.clone(),
+            fn_block,
+            &route,
+        );
+        out.into()
+    } else if capability.to_string().contains("ReadAll") && fn_attrname.is_some() {
+        // Only a `ReadAll` of `#[capabilities(ReadAll, query)]` takes an argument, the query.
+        let query_struct = get_query_struct(&item_struct);
+        let out = impl_readall_query_function_trait(
+            &s.unwrap().sig,
+            &item_struct,
+            query_struct.clone(),
+            fn_attrname,
+            capability.clone(),
+            fn_block,
+            &route,
+        );
+        out.into()
+    } else if capability.to_string().contains("ReadAll") {
         let action_struct = format_ident!("EmptyInput");
         let out = impl_readall_function_trait(
-            fn_signature,
+            &s.unwrap().sig,
             action_struct,
-            item_struct,
+            item_type.clone(),
             item_cap,
-            capability,
+            capability_bound.clone(),
             fn_block,
+            &route,
+        );
+        out.into()
+    } else if capability.to_string().contains("CreateAll") {
+        if fn_attrname.is_none() {
+            fn_attrname
+                .span()
+                .unstable()
+                .error("Missing argument for function, pass in the data you are creating")
+                .emit();
+        }
+
+        let out = impl_createall_function_trait(
+            &s.unwrap().sig,
+            item_type.clone(),
+            item_cap,
+            fn_attrname,
+            capability_bound.clone(),
+            fn_block,
+            &route,
         );
         out.into()
     } else if capability.to_string().contains("UpdateAll") {
         let out = impl_updateall_function_trait(
-            fn_signature,
-            item_struct,
+            &s.unwrap().sig,
+            item_type.clone(),
             item_cap,
             fn_attrname,
-            capability,
+            capability_bound.clone(),
             fn_block,
+            &route,
         );
         out.into()
     } else if capability.to_string().contains("DeleteAll") {
@@ -380,24 +648,26 @@ pub fn capability(args: TokenStream, annotated_item: TokenStream) -> TokenStream
         }
 
         let out = impl_deleteall_function_trait(
-            fn_signature,
-            item_struct,
+            &s.unwrap().sig,
+            item_type.clone(),
             item_cap,
             fn_attrname,
-            capability,
+            capability_bound.clone(),
             fn_block,
+            &route,
         );
         out.into()
     } else if 
         capability.to_string().eq(&format!("{}{}{}", CAP_PREFIX, "Delete", item_struct)) 
     {
         let out = impl_delete_function_trait(
-            fn_signature,
-            item_struct,
+            &s.unwrap().sig,
+            item_type.clone(),
             item_cap,
             fn_attrname,
-            capability,
+            capability_bound.clone(),
             fn_block,
+            &route,
         );
 
         out.into()
@@ -405,14 +675,15 @@ pub fn capability(args: TokenStream, annotated_item: TokenStream) -> TokenStream
     capability.to_string().eq(&format!(
         "{}{}{}{}",
         CAP_PREFIX, "Delete", item_struct, "Id"
-    )) {
+    )) || by_key("Delete") {
         let out = impl_delete_function_trait(
-            fn_signature,
-            action_id.unwrap(),
+            &s.unwrap().sig,
+            quote! { #action_id #id_arguments },
             item_cap,
             fn_attrname,
-            capability,
-            fn_block
+            capability_bound.clone(),
+            fn_block,
+            &route,
         );
         out.into()
     }
@@ -420,54 +691,86 @@ pub fn capability(args: TokenStream, annotated_item: TokenStream) -> TokenStream
         .to_string()
         .eq(&format!("{}{}{}", CAP_PREFIX, "Update", item_struct))
     {
-        let out = impl_update_function_trait(
-            fn_signature,
-            item_struct,
-            item_cap,
-            fn_attrname,
-            capability,
-            fn_block,
-        );
+        let out = match &dto_input {
+            Some(dto) => impl_update_patch_function_trait(
+                &s.unwrap().sig,
+                quote! { #dto },
+                item_cap,
+                fn_attrname,
+                capability_bound.clone(),
+                fn_block,
+                &route,
+            ),
+            None => impl_update_function_trait(
+                &s.unwrap().sig,
+                item_type.clone(),
+                item_cap,
+                fn_attrname,
+                capability_bound.clone(),
+                fn_block,
+                &route,
+            ),
+        };
         out.into()
     } else if  capability.to_string().eq(&format!(
         "{}{}{}{}",
         CAP_PREFIX, "Update", item_struct, "Id"
-    )) {
+    )) || by_key("Update") {
         let out = impl_update_function_trait(
-            fn_signature,
-            action_id.unwrap(),
+            &s.unwrap().sig,
+            quote! { #action_id #id_arguments },
             item_cap,
             fn_attrname,
-            capability,
-            fn_block);
+            capability_bound.clone(),
+            fn_block,
+            &route,
+        );
 
         out.into()
     } else {
-        let action_struct = action_id.as_ref().unwrap().to_owned();
+        let action_id = action_id.as_ref().unwrap();
+        let action_struct = if let Some(dto) = &dto_input {
+            quote! { #dto }
+        } else if action_id == &item_struct {
+            item_type.clone()
+        } else {
+            quote! { #action_id #id_arguments }
+        };
+        let (params, predicates) = get_guard_generics(generics);
+        let perform = get_perform_code(
+            fn_signature,
+            quote! { #item_cap<#action_struct> },
+            quote! { #item_type },
+            quote! { let #fn_attr = action.data; },
+            fn_block,
+            &route,
+            generics,
+        );
+        // A `Read` hands out the projection of the item, `Create` the item it created.
+        let (cap_arg, output, guard) = if item_cap == "Read" {
+            (
+                quote! { grant: impl Into<::capabilities::Grant> },
+                quote! { <#item_type as ::capabilities::Projection>::View },
+                get_projection_guard_code(&item_cap, item_type.clone()),
+            )
+        } else {
+            (
+                quote! { cap: ::capabilities::Capability },
+                item_type.clone(),
+                get_guard_code(&item_cap),
+            )
+        };
         let out = quote! {
 
-            pub async fn #fn_signature<Service>(service: &Service, param: #action_struct, cap: ::capabilities::Capability) -> Result<#item_struct, CapServiceError>
+            pub async fn #fn_signature<#params Service>(service: &Service, param: #action_struct, #cap_arg) -> Result<#output, CapServiceError>
             where
-                Service: #capability,
+                Service: #capability_bound,
+                #predicates
             {
-                let valid = ::capabilities::#item_cap { data: param };
-                if valid.into_enum().eq(&cap) {
-                     service.perform(valid).await
-                } else {
-                    Err(CapServiceError)
-                }
+                #guard
             }
 
-            #[async_trait]
-            impl CapabilityTrait<#item_cap<#action_struct>> for CapService {
-                type Data = #item_struct;
-                type Error = CapServiceError;
-
-                async fn perform(&self, action: #item_cap<#action_id>) -> Result<Self::Data, Self::Error> {
-                    let #fn_attr = action.data;
-                    #fn_block
-                }
-            }
+            #perform
         };
         out
     };
@@ -475,238 +778,485 @@ pub fn capability(args: TokenStream, annotated_item: TokenStream) -> TokenStream
     out.into()
 }
 
+/// A custom verb, its input is the argument of the fn and its output what the fn returns. No
+/// token grants a verb that isn't registered, so the fn fails with `CapServiceError::Config`
+/// until `register_verbs` of the struct is called.
+fn impl_verb_function_trait(
+    sig: &Signature,
+    item_struct: &Ident,
+    verb: Ident,
+    fn_attrname: Option<&Pat>,
+    capability: TokenStream2,
+    fn_block: &Block,
+    route: &TokenStream2,
+) -> TokenStream {
+    let fn_signature = &sig.ident;
+    let generics = &sig.generics;
+    let operation = get_verb_struct(&verb, item_struct);
+    let input = match sig.inputs.first() {
+        Some(Typed(t)) => t.ty.as_ref().clone(),
+        _ => {
+            sig.span()
+                .unstable()
+                .error("Missing argument for function, pass in the input of the verb")
+                .emit();
+            return quote! {}.into();
+        }
+    };
+    let output = match get_result_type(sig) {
+        Some(output) => output.clone(),
+        None => {
+            sig.output
+                .span()
+                .unstable()
+                .error("A capability function returns Result<Output, CapServiceError>")
+                .emit();
+            return quote! {}.into();
+        }
+    };
+
+    let name = verb.to_string().to_lowercase();
+    let unregistered = format!(
+        "verb {} is not registered, call {}::register_verbs",
+        name, item_struct
+    );
+    let guard = get_operation_guard_code(quote! { #operation });
+    let (params, predicates) = get_guard_generics(generics);
+    let perform = get_perform_code(
+        fn_signature,
+        quote! { #operation },
+        quote! { #output },
+        quote! { let #fn_attrname = action.data; },
+        fn_block,
+        route,
+        generics,
+    );
+    let out = quote! {
+
+        pub async fn #fn_signature<#params Service>(service: &Service, param: #input, cap: ::capabilities::Capability) -> Result<#output, CapServiceError>
+        where
+            Service: #capability,
+            #predicates
+        {
+            if ::capabilities::Verb::lookup(#name).is_none() {
+                return Err(CapServiceError::Config(#unregistered.to_string()));
+            }
+            #guard
+        }
+
+        #perform
+    };
+    out.into()
+}
+
 fn impl_readall_function_trait(
-    fn_signature: &Ident,
+    sig: &Signature,
     _action_struct: Ident,
-    item_struct: Ident,
+    item_struct: TokenStream2,
     item_cap: Ident,
-    capability: Ident,
+    capability: TokenStream2,
     fn_block: &Block,
+    route: &TokenStream2,
 ) -> TokenStream {
+    let fn_signature = &sig.ident;
+    let generics = &sig.generics;
+    let guard = get_projection_guard_code(&item_cap, quote! { Vec<#item_struct> });
+    let (params, predicates) = get_guard_generics(generics);
+    let perform = get_perform_code(
+        fn_signature,
+        quote! { #item_cap<Vec<#item_struct>> },
+        quote! { Vec<#item_struct> },
+        quote! {},
+        fn_block,
+        route,
+        generics,
+    );
     let out = quote! {
 
-        pub async fn #fn_signature<Service>(service: &Service, cap: ::capabilities::Capability) -> Result<Vec<#item_struct>, CapServiceError>
+        pub async fn #fn_signature<#params Service>(service: &Service, grant: impl Into<::capabilities::Grant>) -> Result<Vec<<#item_struct as ::capabilities::Projection>::View>, CapServiceError>
         where
             Service: #capability,
+            #predicates
         {
             let param: Vec<#item_struct> = Vec::<#item_struct>::new();
-            let valid = ::capabilities::#item_cap { data: param };
-            if valid.into_enum().eq(&cap) {
-                service.perform(valid).await
-            } else {
-                Err(CapServiceError)
-            }
+            #guard
 
         }
 
-        #[async_trait]
-        impl CapabilityTrait<#item_cap<Vec<#item_struct>>> for CapService {
-            type Data = Vec<#item_struct>;
-            type Error = CapServiceError;
+        #perform
+    };
+    out.into()
+}
+
+/// A `StreamAll`, the lease is checked when the stream is opened and while it is read, see
+/// `capabilities::Lease::guard`.
+fn impl_streamall_function_trait(
+    sig: &Signature,
+    item_struct: TokenStream2,
+    item_cap: Ident,
+    capability: TokenStream2,
+    fn_block: &Block,
+    route: &TokenStream2,
+) -> TokenStream {
+    let fn_signature = &sig.ident;
+    let generics = &sig.generics;
+    let guard = get_guard_code(&item_cap);
+    let (params, predicates) = get_guard_generics(generics);
+    let perform = get_perform_code(
+        fn_signature,
+        quote! { #item_cap<#item_struct> },
+        quote! { ::capabilities::CapStream<#item_struct> },
+        quote! {},
+        fn_block,
+        route,
+        generics,
+    );
+    let out = quote! {
 
-            async fn perform(&self, action: #item_cap<Vec<#item_struct>>) -> Result<Self::Data, Self::Error> {
-                #fn_block
+        pub async fn #fn_signature<#params Service>(service: &Service, lease: impl Into<::capabilities::Lease>) -> Result<::capabilities::CapStream<<#item_struct as ::capabilities::Projection>::View>, CapServiceError>
+        where
+            Service: #capability,
+            #predicates
+        {
+            let lease: ::capabilities::Lease = lease.into();
+            if lease.is_expired() {
+                return Err(CapServiceError::Expired);
+            }
+            let cap = lease.capability();
+            let param = ::std::marker::PhantomData;
+            let stream: Result<::capabilities::CapStream<#item_struct>, CapServiceError> = async {
+                #guard
             }
+            .await;
+            let grant = lease.grant.clone();
+            Ok(::capabilities::Projection::project(lease.guard(stream?), &grant))
         }
+
+        #perform
     };
     out.into()
 }
 
-fn impl_updateall_function_trait(
-    fn_signature: &Ident,
-    item_struct: Ident,
-    item_cap: Ident,
+/// A `ReadAll` returning one `capabilities::Page` of the items the query asks for.
+fn impl_readall_query_function_trait(
+    sig: &Signature,
+    item_struct: &Ident,
+    query_struct: Ident,
     fn_attrname: Option<&Pat>,
     capability: Ident,
     fn_block: &Block,
+    route: &TokenStream2,
 ) -> TokenStream {
+    let fn_signature = &sig.ident;
+    let generics = &sig.generics;
+    let guard = get_projection_guard_code(
+        &format_ident!("ReadAll"),
+        quote! { ::capabilities::Page<#item_struct> },
+    );
+    let (params, predicates) = get_guard_generics(generics);
+    let perform = get_perform_code(
+        fn_signature,
+        quote! { ReadAll<#query_struct> },
+        quote! { ::capabilities::Page<#item_struct> },
+        quote! { let #fn_attrname = action.data; },
+        fn_block,
+        route,
+        generics,
+    );
     let out = quote! {
 
-        pub async fn #fn_signature<Service>(service: &Service, param: Vec<#item_struct>, cap: ::capabilities::Capability) -> Result<(), CapServiceError>
+        pub async fn #fn_signature<#params Service>(service: &Service, param: #query_struct, grant: impl Into<::capabilities::Grant>) -> Result<::capabilities::Page<<#item_struct as ::capabilities::Projection>::View>, CapServiceError>
         where
             Service: #capability,
+            #predicates
         {
-            let valid = ::capabilities::#item_cap { data: param };
-            if valid.into_enum().eq(&cap) {
-                service.perform(valid).await
-            } else {
-                Err(CapServiceError)
-            }
+            #guard
+        }
+
+        #perform
+    };
+    out.into()
+}
+
+fn impl_createall_function_trait(
+    sig: &Signature,
+    item_struct: TokenStream2,
+    item_cap: Ident,
+    fn_attrname: Option<&Pat>,
+    capability: TokenStream2,
+    fn_block: &Block,
+    route: &TokenStream2,
+) -> TokenStream {
+    let fn_signature = &sig.ident;
+    let generics = &sig.generics;
+    let guard = get_guard_code(&item_cap);
+    let (params, predicates) = get_guard_generics(generics);
+    let perform = get_perform_code(
+        fn_signature,
+        quote! { #item_cap<Vec<#item_struct>> },
+        quote! { Vec<#item_struct> },
+        quote! { let #fn_attrname = action.data; },
+        fn_block,
+        route,
+        generics,
+    );
+    let out = quote! {
+
+        pub async fn #fn_signature<#params Service>(service: &Service, param: Vec<#item_struct>, cap: ::capabilities::Capability) -> Result<Vec<#item_struct>, CapServiceError>
+        where
+            Service: #capability,
+            #predicates
+        {
+            #guard
         }
 
-        #[async_trait]
-        impl CapabilityTrait<#item_cap<Vec<#item_struct>>> for CapService {
-            type Data = ();
-            type Error = CapServiceError;
+        #perform
+    };
+    out.into()
+}
 
-            async fn perform(&self, action: #item_cap<Vec<#item_struct>>) -> Result<Self::Data, Self::Error> {
-                let #fn_attrname = action.data;
-                #fn_block
-            }
+fn impl_updateall_function_trait(
+    sig: &Signature,
+    item_struct: TokenStream2,
+    item_cap: Ident,
+    fn_attrname: Option<&Pat>,
+    capability: TokenStream2,
+    fn_block: &Block,
+    route: &TokenStream2,
+) -> TokenStream {
+    let fn_signature = &sig.ident;
+    let generics = &sig.generics;
+    let guard = get_guard_code(&item_cap);
+    let (params, predicates) = get_guard_generics(generics);
+    let perform = get_perform_code(
+        fn_signature,
+        quote! { #item_cap<Vec<#item_struct>> },
+        quote! { () },
+        quote! { let #fn_attrname = action.data; },
+        fn_block,
+        route,
+        generics,
+    );
+    let out = quote! {
+
+        pub async fn #fn_signature<#params Service>(service: &Service, param: Vec<#item_struct>, cap: ::capabilities::Capability) -> Result<(), CapServiceError>
+        where
+            Service: #capability,
+            #predicates
+        {
+            #guard
         }
+
+        #perform
     };
     out.into()
 }
 
 fn impl_deleteall_function_trait(
-    fn_signature: &Ident,
-    item_struct: Ident,
+    sig: &Signature,
+    item_struct: TokenStream2,
     item_cap: Ident,
     fn_attrname: Option<&Pat>,
-    capability: Ident,
+    capability: TokenStream2,
     fn_block: &Block,
+    route: &TokenStream2,
 ) -> TokenStream {
+    let fn_signature = &sig.ident;
+    let generics = &sig.generics;
     let data_accessor = if fn_attrname.is_some() {
         quote! { let #fn_attrname = action.data; }
     } else {
         quote! {}
     };
 
+    let guard = get_guard_code(&item_cap);
+    let (params, predicates) = get_guard_generics(generics);
+    let perform = get_perform_code(
+        fn_signature,
+        quote! { #item_cap<Vec<#item_struct>> },
+        quote! { () },
+        data_accessor,
+        fn_block,
+        route,
+        generics,
+    );
     let out = quote! {
 
-        pub async fn #fn_signature<Service>(service: &Service, param: Vec<#item_struct>, cap: ::capabilities::Capability) -> Result<(), CapServiceError>
+        pub async fn #fn_signature<#params Service>(service: &Service, param: Vec<#item_struct>, cap: ::capabilities::Capability) -> Result<(), CapServiceError>
         where
             Service: #capability,
+            #predicates
         {
-            let valid = ::capabilities::#item_cap { data: param };
-            if valid.into_enum().eq(&cap) {
-                service.perform(valid).await
-            } else {
-                Err(CapServiceError)
-            }
+            #guard
         }
 
-        #[async_trait]
-        impl CapabilityTrait<#item_cap<(Vec<#item_struct>)>> for CapService {
-            type Data = ();
-            type Error = CapServiceError;
-
-            async fn perform(&self, action: #item_cap<Vec<#item_struct>>) -> Result<Self::Data, Self::Error> {
-                #data_accessor
-                #fn_block
-            }
-        }
+        #perform
     };
     out.into()
 }
 
 fn impl_delete_function_trait(
-    fn_signature: &Ident,
-    item_struct: Ident,
+    sig: &Signature,
+    item_struct: TokenStream2,
     item_cap: Ident,
     fn_attrname: Option<&Pat>,
-    capability: Ident,
+    capability: TokenStream2,
     fn_block: &Block,
+    route: &TokenStream2,
 ) -> TokenStream {
-    let _typealias = format_ident!("{}Id", item_struct);
-    //println!("{:#?}: {:#?}",action_struct,  _typealias);
-
+    let fn_signature = &sig.ident;
+    let generics = &sig.generics;
     let data_accessor = if fn_attrname.is_some() {
         quote! { let #fn_attrname = action.data; }
     } else {
         quote! {}
     };
+    let guard = get_guard_code(&item_cap);
+    let (params, predicates) = get_guard_generics(generics);
+    let perform = get_perform_code(
+        fn_signature,
+        quote! { #item_cap<#item_struct> },
+        quote! { () },
+        data_accessor,
+        fn_block,
+        route,
+        generics,
+    );
     let out = quote! {
 
-        pub async fn #fn_signature<Service>(service: &Service, param: #item_struct, cap: ::capabilities::Capability) -> Result<(), CapServiceError>
+        pub async fn #fn_signature<#params Service>(service: &Service, param: #item_struct, cap: ::capabilities::Capability) -> Result<(), CapServiceError>
         where
             Service: #capability,
+            #predicates
         {
-            let valid = ::capabilities::#item_cap { data: param };
-            if valid.into_enum().eq(&cap) {
-                service.perform(valid).await
-            } else {
-                Err(CapServiceError)
-            }
+            #guard
         }
 
-        #[async_trait]
-        impl CapabilityTrait<#item_cap<#item_struct>> for CapService {
-            type Data = ();
-            type Error = CapServiceError;
-
-            async fn perform(&self, action: #item_cap<#item_struct>) -> Result<Self::Data, Self::Error> {
-                #data_accessor
-                #fn_block
-            }
-        }
+        #perform
     };
     out.into()
 }
 
 fn impl_update_function_trait(
-    fn_signature: &Ident,
-    item_struct: Ident,
+    sig: &Signature,
+    item_struct: TokenStream2,
     item_cap: Ident,
     fn_attrname: Option<&Pat>,
-    capability: Ident,
+    capability: TokenStream2,
     fn_block: &Block,
+    route: &TokenStream2,
 ) -> TokenStream {
+    let fn_signature = &sig.ident;
+    let generics = &sig.generics;
+    let guard = get_guard_code(&item_cap);
+    let (params, predicates) = get_guard_generics(generics);
+    let perform = get_perform_code(
+        fn_signature,
+        quote! { #item_cap<#item_struct> },
+        quote! { () },
+        quote! { let #fn_attrname = action.data; },
+        fn_block,
+        route,
+        generics,
+    );
     let out = quote! {
 
-        pub async fn #fn_signature<Service>(service: &Service, param: #item_struct, cap: ::capabilities::Capability) -> Result<(), CapServiceError>
+        pub async fn #fn_signature<#params Service>(service: &Service, param: #item_struct, cap: ::capabilities::Capability) -> Result<(), CapServiceError>
         where
             Service: #capability,
+            #predicates
         {
-            let valid = ::capabilities::#item_cap { data: param };
-            if valid.into_enum().eq(&cap) {
-                service.perform(valid).await
-            } else {
-                Err(CapServiceError)
-            }
+            #guard
         }
 
-        #[async_trait]
-        impl CapabilityTrait<#item_cap<#item_struct>> for CapService {
-            type Data = ();
-            type Error = CapServiceError;
+        #perform
+    };
+    out.into()
+}
 
-            async fn perform(&self, action: #item_cap<#item_struct>) -> Result<Self::Data, Self::Error> {
-                let #fn_attrname = action.data;
-                #fn_block
-            }
+/// An `Update` taking `XPatch`, whose fields are checked against the `capabilities::Grant`
+/// before the guard. A grant of single fields passes the guard as `Capability::Update`.
+fn impl_update_patch_function_trait(
+    sig: &Signature,
+    patch_struct: TokenStream2,
+    item_cap: Ident,
+    fn_attrname: Option<&Pat>,
+    capability: TokenStream2,
+    fn_block: &Block,
+    route: &TokenStream2,
+) -> TokenStream {
+    let fn_signature = &sig.ident;
+    let generics = &sig.generics;
+    let guard = get_guard_code(&item_cap);
+    let (params, predicates) = get_guard_generics(generics);
+    let perform = get_perform_code(
+        fn_signature,
+        quote! { #item_cap<#patch_struct> },
+        quote! { () },
+        quote! { let #fn_attrname = action.data; },
+        fn_block,
+        route,
+        generics,
+    );
+    let out = quote! {
+
+        pub async fn #fn_signature<#params Service>(service: &Service, param: #patch_struct, grant: impl Into<::capabilities::Grant>) -> Result<(), CapServiceError>
+        where
+            Service: #capability,
+            #predicates
+        {
+            let grant: ::capabilities::Grant = grant.into();
+            param.check(&grant)?;
+            let cap = match grant.capability {
+                ::capabilities::Capability::UpdateFields => ::capabilities::Capability::Update,
+                cap => cap,
+            };
+            #guard
         }
+
+        #perform
     };
     out.into()
 }
 
 fn _impl_deleteid_function_trait(
-    fn_signature: &Ident,
-    item_struct: Ident,
+    sig: &Signature,
+    item_struct: TokenStream2,
     item_cap: Ident,
     fn_attrname: Option<&Pat>,
-    capability: Ident,
+    capability: TokenStream2,
     fn_block: &Block,
+    route: &TokenStream2,
 ) -> TokenStream {
+    let fn_signature = &sig.ident;
+    let generics = &sig.generics;
     let data_accessor = if fn_attrname.is_some() {
         quote! { let #fn_attrname = action.data; }
     } else {
         quote! {}
     };
+    let guard = get_guard_code(&item_cap);
+    let (params, predicates) = get_guard_generics(generics);
+    let perform = get_perform_code(
+        fn_signature,
+        quote! { #item_cap<#item_struct> },
+        quote! { () },
+        data_accessor,
+        fn_block,
+        route,
+        generics,
+    );
     let out = quote! {
 
-        pub async fn #fn_signature<Service>(service: &Service, param: #item_struct, cap: Capability) -> Result<(), CapServiceError>
+        pub async fn #fn_signature<#params Service>(service: &Service, param: #item_struct, cap: Capability) -> Result<(), CapServiceError>
         where
             Service: #capability,
+            #predicates
         {
-            let valid = ::capabilities::#item_cap { data: param };
-            if valid.into_enum().eq(&cap) {
-                service.perform(valid).await
-            } else {
-                Err(CapServiceError)
-            }
+            #guard
         }
 
-        #[async_trait]
-        impl CapabilityTrait<#item_cap<#item_struct>> for CapService {
-            type Data = ();
-            type Error = CapServiceError;
-
-            async fn perform(&self, action: #item_cap<#item_struct>) -> Result<Self::Data, Self::Error> {
-                #data_accessor
-                #fn_block
-            }
-        }
+        #perform
     };
     out.into()
 }
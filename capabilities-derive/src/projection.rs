@@ -0,0 +1,125 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{Attribute, Field, Fields, Ident, Item, Meta, NestedMeta};
+
+use crate::dto::is_cap_attr;
+use crate::helpers::get_snake_case;
+
+const SENSITIVE: &str = "sensitive";
+
+/// `PersonView`, what `Read` returns of a struct with `#[cap(sensitive)]` fields.
+pub fn get_view_struct(struct_name: &Ident) -> Ident {
+    format_ident!("{}View", struct_name)
+}
+
+/// `#[cap(sensitive)]`, the attribute itself is checked by `strip_cap_attrs` and `impl_dto`.
+fn is_sensitive(field: &Field) -> bool {
+    field
+        .attrs
+        .iter()
+        .filter(|a| is_cap_attr(a))
+        .any(|a| match a.parse_meta() {
+            Ok(Meta::List(list)) => list.nested.iter().any(|n| match n {
+                NestedMeta::Meta(Meta::Path(p)) => p.is_ident(SENSITIVE),
+                _ => false,
+            }),
+            _ => false,
+        })
+}
+
+fn derives(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().filter(|a| a.path.is_ident("derive")).any(|a| {
+        a.tokens
+            .to_string()
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|d| d == name)
+    })
+}
+
+/// `capabilities::Projection` of an item, what its `Read`, `ReadAll` and `StreamAll` return.
+///
+/// Without sensitive fields the view is the item. With them it is `XView`, deriving what the
+/// struct derives, where a sensitive field is `None` and left out of the JSON unless the grant
+/// has `read:x.field`. The generated fns only hand out the view, so the item never reaches a
+/// caller without the grant.
+pub fn impl_projection(item: &Item) -> TokenStream2 {
+    let (ident, generics, item_struct) = match item {
+        Item::Struct(s) => (&s.ident, &s.generics, Some(s)),
+        Item::Enum(e) => (&e.ident, &e.generics, None),
+        _ => return quote! {},
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let sensitive: Vec<&Field> = item_struct
+        .map(|s| s.fields.iter().filter(|f| is_sensitive(f)).collect())
+        .unwrap_or_default();
+
+    let item_struct = match item_struct {
+        Some(item_struct) if !sensitive.is_empty() => item_struct,
+        _ => {
+            return quote! {
+                impl #impl_generics ::capabilities::Projection for #ident #ty_generics #where_clause {
+                    type View = Self;
+
+                    fn project(self, _grant: &::capabilities::Grant) -> Self {
+                        self
+                    }
+                }
+            }
+        }
+    };
+    if !generics.params.is_empty() || !matches!(item_struct.fields, Fields::Named(_)) {
+        sensitive[0]
+            .span()
+            .unstable()
+            .error("sensitive needs a struct with named fields and without generics")
+            .emit();
+        return quote! {};
+    }
+
+    let view_struct = get_view_struct(ident);
+    let resource = get_snake_case(ident);
+    let derives_serialize = derives(&item_struct.attrs, "Serialize");
+    let derive_attrs: Vec<&Attribute> = item_struct
+        .attrs
+        .iter()
+        .filter(|a| a.path.is_ident("derive"))
+        .collect();
+
+    let mut view_fields = vec![];
+    let mut projected = vec![];
+    for f in &item_struct.fields {
+        let ident = f.ident.as_ref().unwrap();
+        let ty = &f.ty;
+        let attrs: Vec<&Attribute> = f.attrs.iter().filter(|a| !is_cap_attr(a)).collect();
+        if sensitive.contains(&f) {
+            let skip = derives_serialize
+                .then(|| quote! { #[serde(skip_serializing_if = "Option::is_none")] });
+            let name = ident.to_string();
+            view_fields.push(quote! { #( #attrs )* #skip pub #ident: Option<#ty> });
+            projected.push(quote! {
+                #ident: grant.allows_read(#resource, #name).then(|| self.#ident)
+            });
+        } else {
+            view_fields.push(quote! { #( #attrs )* pub #ident: #ty });
+            projected.push(quote! { #ident: self.#ident });
+        }
+    }
+
+    quote! {
+        #( #derive_attrs )*
+        pub struct #view_struct {
+            #( #view_fields, )*
+        }
+
+        impl ::capabilities::Projection for #ident {
+            type View = #view_struct;
+
+            fn project(self, grant: &::capabilities::Grant) -> #view_struct {
+                #view_struct {
+                    #( #projected, )*
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    Fields, GenericArgument, Ident, ItemStruct, Meta, NestedMeta, Path, PathArguments, Type,
+};
+
+use crate::helpers::get_cap_macro;
+
+pub const QUERY: &str = "query";
+
+const FILTER_TYPES: [&str; 11] = [
+    "i8", "i16", "i32", "i64", "u8", "u16", "u32", "f32", "f64", "bool", "String",
+];
+
+/// The `query` flag of `#[capabilities(ReadAll, query)]`.
+pub fn parse_field_args_for_query(attr_args: &[NestedMeta]) -> Option<Path> {
+    attr_args.iter().find_map(|i| match i {
+        NestedMeta::Meta(Meta::Path(p)) if p.is_ident(QUERY) => Some(p.clone()),
+        _ => None,
+    })
+}
+
+/// `XQuery`, the input of a `ReadAll` taking a query.
+pub fn get_query_struct(struct_name: &Ident) -> Ident {
+    format_ident!("{}Query", struct_name)
+}
+
+/// The type a field is filtered by and whether the field is an `Option` of it, `None` for the
+/// types `capabilities::FilterValue` has no variant for.
+fn get_filter_type(ty: &Type) -> Option<(&Type, bool)> {
+    let segment = match ty {
+        Type::Path(p) => p.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident == "Option" {
+        let inner = match &segment.arguments {
+            PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+                GenericArgument::Type(t) => Some(t),
+                _ => None,
+            })?,
+            _ => return None,
+        };
+        let (inner, optional) = get_filter_type(inner)?;
+        return (!optional).then_some((inner, true));
+    }
+    FILTER_TYPES
+        .iter()
+        .any(|t| segment.ident == t && segment.arguments.is_empty())
+        .then_some((ty, false))
+}
+
+fn get_variant(field: &Ident) -> Ident {
+    let mut name = String::new();
+    for part in field.to_string().trim_start_matches("r#").split('_') {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.extend(first.to_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    format_ident!("{}", name)
+}
+
+/// `XField`, `XFilter` and `XQuery` for the fields of integer, float, bool and `String` types and
+/// `Option` of those. `CapReadAllX` takes the query instead of `Vec<X>` and returns a
+/// `capabilities::Page`.
+pub fn impl_query(query: &Path, item_struct: &ItemStruct) -> TokenStream2 {
+    if !matches!(item_struct.fields, Fields::Named(_)) {
+        query
+            .span()
+            .unstable()
+            .error("Queries filter and sort by field names, query needs a struct with named fields")
+            .emit();
+        return quote! {};
+    }
+
+    let struct_name = &item_struct.ident;
+    let field_enum = format_ident!("{}Field", struct_name);
+    let filter_struct = format_ident!("{}Filter", struct_name);
+    let query_struct = get_query_struct(struct_name);
+    let capid = format_ident!("CapReadAll{}", struct_name);
+
+    let mut fields = vec![];
+    let mut variants = vec![];
+    let mut columns = vec![];
+    let mut types = vec![];
+    let mut matches = vec![];
+    for f in &item_struct.fields {
+        let (ident, (ty, optional)) = match (&f.ident, get_filter_type(&f.ty)) {
+            (Some(ident), Some(filter)) => (ident, filter),
+            _ => continue,
+        };
+        matches.push(if optional {
+            quote! { item.#ident.as_ref() == Some(v) }
+        } else {
+            quote! { &item.#ident == v }
+        });
+        fields.push(ident);
+        variants.push(get_variant(ident));
+        columns.push(ident.to_string().trim_start_matches("r#").to_string());
+        types.push(ty);
+    }
+    if fields.is_empty() {
+        query
+            .span()
+            .unstable()
+            .error("No field to query by, supported are integers, floats, bool, String and Option of those")
+            .emit();
+        return quote! {};
+    }
+    let type_names: Vec<String> = types.iter().map(|t| quote!(#t).to_string()).collect();
+
+    let capmacro = get_cap_macro();
+    quote! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum #field_enum {
+            #( #variants, )*
+        }
+
+        impl ::capabilities::QueryField for #field_enum {
+            type Item = #struct_name;
+
+            fn column(&self) -> &'static str {
+                match *self {
+                    #( #field_enum::#variants => #columns, )*
+                }
+            }
+
+            fn from_column(column: &str) -> Option<Self> {
+                match column {
+                    #( #columns => Some(#field_enum::#variants), )*
+                    _ => None,
+                }
+            }
+
+            fn compare(&self, a: &#struct_name, b: &#struct_name) -> ::std::cmp::Ordering {
+                match *self {
+                    #(
+                        #field_enum::#variants => a.#fields
+                            .partial_cmp(&b.#fields)
+                            .unwrap_or(::std::cmp::Ordering::Equal),
+                    )*
+                }
+            }
+        }
+
+        #[derive(Debug, Clone, Default, PartialEq)]
+        pub struct #filter_struct {
+            #( pub #fields: Option<#types>, )*
+        }
+
+        impl ::capabilities::QueryFilter for #filter_struct {
+            type Item = #struct_name;
+
+            fn conditions(&self) -> Vec<(&'static str, ::capabilities::FilterValue)> {
+                let mut conditions = vec![];
+                #(
+                    if let Some(v) = &self.#fields {
+                        conditions.push((#columns, ::capabilities::FilterValue::from(v.clone())));
+                    }
+                )*
+                conditions
+            }
+
+            fn matches(&self, item: &#struct_name) -> bool {
+                #(
+                    if let Some(v) = &self.#fields {
+                        if !(#matches) {
+                            return false;
+                        }
+                    }
+                )*
+                true
+            }
+
+            fn params(&self) -> Vec<(&'static str, String)> {
+                let mut params = vec![];
+                #(
+                    if let Some(v) = &self.#fields {
+                        params.push((#columns, v.to_string()));
+                    }
+                )*
+                params
+            }
+
+            fn set_param(&mut self, column: &str, value: &str) -> Result<bool, String> {
+                match column {
+                    #(
+                        #columns => {
+                            let v = value
+                                .parse()
+                                .map_err(|_| format!("{} takes {}, not {}", column, #type_names, value))?;
+                            self.#fields = Some(v);
+                        }
+                    )*
+                    _ => return Ok(false),
+                }
+                Ok(true)
+            }
+        }
+
+        pub type #query_struct = ::capabilities::Query<#filter_struct, #field_enum>;
+
+        #capmacro
+        cap!( #capid for CapService, composing { ::capabilities::ReadAll<#query_struct>, ::capabilities::Page<#struct_name>, CapServiceError});
+        impl CapToEnum for ::capabilities::ReadAll<#query_struct> {
+            fn into_enum(&self) -> ::capabilities::Capability {
+                ::capabilities::Capability::ReadAll
+            }
+        }
+    }
+}
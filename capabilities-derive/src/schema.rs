@@ -0,0 +1,164 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{
+    Fields, GenericArgument, Ident, ItemStruct, Lit, LitStr, Meta, NestedMeta, PathArguments, Type,
+};
+
+const TABLE: &str = "table";
+
+#[derive(Clone, Copy)]
+enum Dialect {
+    Sqlite,
+    Postgres,
+    MySql,
+}
+
+pub fn parse_field_args_for_table(attr_args: &Vec<NestedMeta>) -> Option<LitStr> {
+    let mut table = None;
+    for i in attr_args {
+        if let NestedMeta::Meta(Meta::NameValue(nv)) = i {
+            if !nv.path.is_ident(TABLE) {
+                continue;
+            }
+            match &nv.lit {
+                Lit::Str(name) => table = Some(name.to_owned()),
+                lit => lit
+                    .span()
+                    .unstable()
+                    .error("table takes the name of the table")
+                    .emit(),
+            }
+        }
+    }
+    table
+}
+
+fn get_last_segment(ty: &Type) -> Option<(String, Option<&Type>)> {
+    let path = match ty {
+        Type::Path(p) => &p.path,
+        _ => return None,
+    };
+    let segment = path.segments.last()?;
+    let inner = match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(t) => Some(t),
+            _ => None,
+        }),
+        _ => None,
+    };
+    Some((segment.ident.to_string(), inner))
+}
+
+/// Maps a field type to its column type, `None` when we do not know the type.
+fn get_column_type(ty: &Type, dialect: Dialect) -> Option<(&'static str, bool)> {
+    let (name, inner) = get_last_segment(ty)?;
+    if name == "Option" {
+        let (column, _) = get_column_type(inner?, dialect)?;
+        return Some((column, true));
+    }
+    if name == "Vec" {
+        let (inner, _) = get_last_segment(inner?)?;
+        return if inner == "u8" {
+            match dialect {
+                Dialect::Postgres => Some(("BYTEA", false)),
+                _ => Some(("BLOB", false)),
+            }
+        } else {
+            None
+        };
+    }
+    let column = match (name.as_str(), dialect) {
+        ("bool", _) => "BOOLEAN",
+        ("i8", Dialect::Sqlite) | ("i16", Dialect::Sqlite) => "INTEGER",
+        ("i8", Dialect::Postgres) | ("i16", Dialect::Postgres) => "SMALLINT",
+        ("i8", Dialect::MySql) => "TINYINT",
+        ("i16", Dialect::MySql) => "SMALLINT",
+        ("i32", _) | ("u16", _) => "INTEGER",
+        ("i64", Dialect::Sqlite) | ("u32", Dialect::Sqlite) => "INTEGER",
+        ("i64", _) | ("u32", _) => "BIGINT",
+        ("f32", _) => "REAL",
+        ("f64", Dialect::Sqlite) => "REAL",
+        ("f64", Dialect::Postgres) => "DOUBLE PRECISION",
+        ("f64", Dialect::MySql) => "DOUBLE",
+        ("String", Dialect::MySql) => "VARCHAR(255)",
+        ("String", _) => "TEXT",
+        _ => return None,
+    };
+    Some((column, false))
+}
+
+fn get_create_table(
+    table: &str,
+    item_struct: &ItemStruct,
+    primary_key: &[Ident],
+    dialect: Dialect,
+) -> String {
+    let mut columns = vec![];
+    for f in &item_struct.fields {
+        let ident = match f.ident.as_ref() {
+            Some(ident) => ident,
+            None => continue,
+        };
+        // Unknown types are reported by `impl_table_schema`.
+        match get_column_type(&f.ty, dialect) {
+            Some((column, true)) => columns.push(format!("    {} {}", ident, column)),
+            Some((column, false)) => columns.push(format!("    {} {} NOT NULL", ident, column)),
+            None => continue,
+        }
+    }
+    if !primary_key.is_empty() {
+        let keys: Vec<String> = primary_key.iter().map(|k| k.to_string()).collect();
+        columns.push(format!("    PRIMARY KEY ({})", keys.join(", ")));
+    }
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (\n{}\n);",
+        table,
+        columns.join(",\n")
+    )
+}
+
+/// `impl capabilities::TableSchema` for a `#[capabilities(..., table = "name")]` struct.
+pub fn impl_table_schema(
+    table: &LitStr,
+    item_struct: &ItemStruct,
+    primary_key: &[Ident],
+) -> TokenStream2 {
+    if let Fields::Unnamed(fields) = &item_struct.fields {
+        fields
+            .span()
+            .unstable()
+            .error("Columns are named after the fields, a table needs a struct with named fields")
+            .emit();
+        return quote! {};
+    }
+
+    for f in &item_struct.fields {
+        if get_column_type(&f.ty, Dialect::Sqlite).is_none() {
+            f.ty.span()
+                .unstable()
+                .error("No column type for this field, supported are integers, floats, bool, String, Vec<u8> and Option of those")
+                .emit();
+        }
+    }
+
+    let struct_name = &item_struct.ident;
+    let name = table.value();
+    let sqlite = get_create_table(&name, item_struct, primary_key, Dialect::Sqlite);
+    let postgres = get_create_table(&name, item_struct, primary_key, Dialect::Postgres);
+    let mysql = get_create_table(&name, item_struct, primary_key, Dialect::MySql);
+
+    quote! {
+        impl ::capabilities::TableSchema for #struct_name {
+            const TABLE: &'static str = #table;
+
+            fn create_table(dialect: ::capabilities::Dialect) -> &'static str {
+                match dialect {
+                    ::capabilities::Dialect::Sqlite => #sqlite,
+                    ::capabilities::Dialect::Postgres => #postgres,
+                    ::capabilities::Dialect::MySql => #mysql,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::future::Future;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use sqlx::pool::Pool;
+use sqlx::sqlite::Sqlite;
+use sqlx::{Database, MySql, Postgres};
+
+use crate::PoolConfig;
+
+/// A type that can be used as the backend of a `#[service]`.
+///
+/// `#[service(MyBackend)]` accepts any type implementing this trait, the
+/// generated `CapService::build` takes `MyBackend::Config` and hands it to `build`.
+#[async_trait]
+pub trait ServiceBackend: Sized + Clone + Send + Sync {
+    type Config: Send;
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    async fn build(conf: Self::Config) -> Result<Self, Self::Error>;
+
+    async fn close(&self) {}
+
+    async fn health(&self) -> Result<(), Self::Error>;
+}
+
+macro_rules! pool_backend {
+    ($database:ty, $connect:ident) => {
+        #[async_trait]
+        impl ServiceBackend for Pool<$database> {
+            type Config = PoolConfig;
+            type Error = sqlx::Error;
+
+            async fn build(conf: Self::Config) -> Result<Self, Self::Error> {
+                connect_with_retries(conf, $connect).await
+            }
+
+            /// Waits for the connections in use to be returned.
+            async fn close(&self) {
+                Pool::close(self).await
+            }
+
+            async fn health(&self) -> Result<(), Self::Error> {
+                sqlx::query("SELECT 1").execute(self).await?;
+                Ok(())
+            }
+        }
+    };
+}
+
+/// Connects, trying again with a growing backoff while the database is unavailable, see
+/// `PoolConfig::connect_retries`.
+async fn connect_with_retries<P, F, Fut>(conf: PoolConfig, connect: F) -> Result<P, sqlx::Error>
+where
+    F: Fn(PoolConfig) -> Fut,
+    Fut: Future<Output = Result<P, sqlx::Error>>,
+{
+    let mut backoff = conf.retry_backoff();
+    let mut retries = conf.connect_retries();
+    loop {
+        match connect(conf.clone()).await {
+            Err(e) if retries > 0 && is_unavailable(&e) => {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                retries -= 1;
+            }
+            pool => return pool,
+        }
+    }
+}
+
+/// Errors of a database that is not up yet, as opposed to a wrong configuration.
+fn is_unavailable(error: &sqlx::Error) -> bool {
+    matches!(
+        error,
+        sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut
+    )
+}
+
+async fn connect_sqlite(conf: PoolConfig) -> Result<Pool<Sqlite>, sqlx::Error> {
+    let options = conf.sqlite_options()?;
+    if conf.lazy {
+        Ok(conf.pool_options().connect_lazy_with(options))
+    } else {
+        conf.pool_options().connect_with(options).await
+    }
+}
+
+async fn connect_pool<DB: Database>(conf: PoolConfig) -> Result<Pool<DB>, sqlx::Error> {
+    if conf.lazy {
+        conf.pool_options().connect_lazy(&conf.url)
+    } else {
+        conf.pool_options().connect(&conf.url).await
+    }
+}
+
+pool_backend!(Sqlite, connect_sqlite);
+pool_backend!(Postgres, connect_pool);
+pool_backend!(MySql, connect_pool);
+
+#[async_trait]
+impl ServiceBackend for Client {
+    type Config = ();
+    type Error = reqwest::Error;
+
+    async fn build(_conf: Self::Config) -> Result<Self, Self::Error> {
+        Ok(Client::new())
+    }
+
+    async fn health(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::pool::DEFAULT_CONNECT_RETRIES;
+
+    #[tokio::test]
+    async fn retry_unavailable_database_by_default() {
+        let mut conf = PoolConfig::new("sqlite::memory:");
+        conf.retry_backoff = Some(Duration::from_millis(1));
+
+        let attempts = AtomicU32::new(0);
+        let pool = connect_with_retries(conf.clone(), |_| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(sqlx::Error::PoolTimedOut)
+        })
+        .await;
+        assert!(matches!(pool, Err(sqlx::Error::PoolTimedOut)));
+        assert_eq!(attempts.load(Ordering::SeqCst), DEFAULT_CONNECT_RETRIES + 1);
+
+        // A wrong configuration does not get better by waiting.
+        let attempts = AtomicU32::new(0);
+        let pool = connect_with_retries(conf, |_| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(sqlx::Error::Configuration("bad url".into()))
+        })
+        .await;
+        assert!(pool.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
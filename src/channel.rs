@@ -0,0 +1,160 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, Mutex as AsyncMutex};
+
+use crate::{Authorized, CapServiceError, Capability, Consume, Publish, ServiceBackend};
+
+/// Messages a channel holds before `publish` waits for consumers.
+pub const DEFAULT_CAPACITY: usize = 64;
+
+struct Channel<T> {
+    sender: mpsc::Sender<T>,
+    // Consumers take turns, every message is consumed once.
+    receiver: AsyncMutex<mpsc::Receiver<T>>,
+}
+
+/// The `ChannelService` backend, one in-process queue per message type.
+///
+/// The queues are created on first use and never handed out, messages only pass through
+/// the `Publish` and `Consume` operations.
+#[derive(Clone)]
+pub struct ChannelService {
+    capacity: usize,
+    channels: Arc<Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>>,
+}
+
+impl ChannelService {
+    pub fn new(capacity: usize) -> Self {
+        ChannelService {
+            capacity,
+            channels: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn channel<T: Send + 'static>(&self) -> Arc<Channel<T>> {
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        let channel = channels
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| {
+                let (sender, receiver) = mpsc::channel::<T>(self.capacity);
+                Arc::new(Channel {
+                    sender,
+                    receiver: AsyncMutex::new(receiver),
+                })
+            })
+            .clone();
+        // Keyed by the TypeId of T, so it is a Channel<T>.
+        channel.downcast::<Channel<T>>().unwrap()
+    }
+
+    /// Takes the `Authorized` of the guarded `publish_x` fns, so there is no way to a queue
+    /// without presenting `Capability::Publish`.
+    pub async fn publish<T: Send + 'static>(
+        &self,
+        operation: Publish<T>,
+        authorized: Authorized,
+    ) -> Result<(), CapServiceError> {
+        authorized.require(Capability::Publish)?;
+        self.channel::<T>()
+            .sender
+            .send(operation.data)
+            .await
+            .map_err(|_| CapServiceError::Config("Channel is closed".to_string()))
+    }
+
+    /// Waits for the next message of type `T`, given the `Authorized` of `Capability::Consume`.
+    pub async fn consume<T: Send + 'static>(
+        &self,
+        _operation: Consume<PhantomData<T>>,
+        authorized: Authorized,
+    ) -> Result<T, CapServiceError> {
+        authorized.require(Capability::Consume)?;
+        let channel = self.channel::<T>();
+        let mut receiver = channel.receiver.lock().await;
+        // The channel keeps its own sender, so it never runs dry for good.
+        receiver.recv().await.ok_or(CapServiceError::NotFound)
+    }
+}
+
+impl Default for ChannelService {
+    fn default() -> Self {
+        ChannelService::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[async_trait]
+impl ServiceBackend for ChannelService {
+    /// The capacity of every channel.
+    type Config = usize;
+    type Error = CapServiceError;
+
+    async fn build(conf: Self::Config) -> Result<Self, Self::Error> {
+        if conf == 0 {
+            return Err(CapServiceError::Config(
+                "Channels need a capacity of at least 1".to_string(),
+            ));
+        }
+        Ok(ChannelService::new(conf))
+    }
+
+    async fn health(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn authorized(capability: Capability) -> Authorized {
+        Authorized::check(capability, capability).unwrap()
+    }
+
+    #[tokio::test]
+    async fn one_channel_per_message_type() {
+        let channels = ChannelService::default();
+        channels
+            .publish(Publish { data: 1u32 }, authorized(Capability::Publish))
+            .await
+            .unwrap();
+        channels
+            .publish(
+                Publish {
+                    data: "text".to_string(),
+                },
+                authorized(Capability::Publish),
+            )
+            .await
+            .unwrap();
+
+        let text: String = channels
+            .consume(
+                Consume { data: PhantomData },
+                authorized(Capability::Consume),
+            )
+            .await
+            .unwrap();
+        let number: u32 = channels
+            .consume(
+                Consume { data: PhantomData },
+                authorized(Capability::Consume),
+            )
+            .await
+            .unwrap();
+        assert_eq!(text, "text");
+        assert_eq!(number, 1);
+    }
+
+    #[tokio::test]
+    async fn publish_needs_publish() {
+        let channels = ChannelService::default();
+        let denied = channels
+            .publish(Publish { data: 1u32 }, authorized(Capability::Consume))
+            .await;
+        assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt::Display;
+
+use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::{CapServiceError, Capability, Page, Query, QueryField, QueryFilter, WebService};
+
+/// The JSON body of a `Denied` response, so that clients can rebuild the error.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct DeniedBody {
+    pub required: Capability,
+    pub presented: Capability,
+}
+
+/// Calls one resource of a capability server, laid out as
+///
+/// | Capability | Request                 |
+/// |------------|-------------------------|
+/// | Read       | `GET {path}/{id}`       |
+/// | ReadAll    | `GET {path}`            |
+/// | ReadAll    | `GET {path}?{query}`    |
+/// | Create     | `POST {path}`           |
+/// | Update     | `PUT {path}/{id}`       |
+/// | UpdateAll  | `PUT {path}`            |
+/// | Delete     | `DELETE {path}/{id}`    |
+/// | DeleteAll  | `DELETE {path}`         |
+///
+/// with JSON bodies, `DeleteAll` sends the items it deletes. Update, UpdateAll, Delete and
+/// DeleteAll return nothing, like the capabilities, so their responses are not decoded. The
+/// clients generated by `#[capabilities(..., client = "/path")]` wrap it.
+#[derive(Debug, Clone)]
+pub struct RemoteClient {
+    service: WebService,
+    path: String,
+}
+
+impl RemoteClient {
+    pub fn new(service: WebService, path: impl Into<String>) -> Self {
+        RemoteClient {
+            service,
+            path: path.into().trim_matches('/').to_string(),
+        }
+    }
+
+    pub fn service(&self) -> &WebService {
+        &self.service
+    }
+
+    /// `{path}/{id}` with every part of the id a percent-encoded segment of its own, so that no
+    /// id addresses another resource. `.` and `..` would be resolved away, they are refused.
+    fn item_url(&self, id: &[&(dyn Display + Sync)]) -> Result<String, CapServiceError> {
+        let segments: Vec<String> = id.iter().map(|part| part.to_string()).collect();
+        if let Some(part) = segments
+            .iter()
+            .find(|part| part.is_empty() || *part == "." || *part == "..")
+        {
+            return Err(CapServiceError::Config(format!(
+                "{:?} can't be a segment of an id",
+                part
+            )));
+        }
+        let mut url = self.service.url(&self.path)?;
+        url.path_segments_mut()
+            .map_err(|_| CapServiceError::Config(format!("{} has no path", self.path)))?
+            .pop_if_empty()
+            .extend(&segments);
+        Ok(url.into())
+    }
+
+    pub async fn read<T: DeserializeOwned>(
+        &self,
+        id: &[&(dyn Display + Sync)],
+    ) -> Result<T, CapServiceError> {
+        let response = self
+            .service
+            .authed_get(&self.item_url(id)?)
+            .await?
+            .send()
+            .await?;
+        let response = check(response, Capability::Read).await?;
+        Ok(response.json().await?)
+    }
+
+    pub async fn read_all<T: DeserializeOwned>(&self) -> Result<T, CapServiceError> {
+        let response = self.service.authed_get(&self.path).await?.send().await?;
+        let response = check(response, Capability::ReadAll).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Reads one page of a `ReadAll` with `query`, see `Query::params`.
+    pub async fn read_page<F, S, T>(&self, query: &Query<F, S>) -> Result<Page<T>, CapServiceError>
+    where
+        F: QueryFilter,
+        S: QueryField<Item = F::Item>,
+        T: DeserializeOwned,
+    {
+        let request = self
+            .service
+            .authed_get(&self.path)
+            .await?
+            .query(&query.params());
+        let response = check(request.send().await?, Capability::ReadAll).await?;
+        Ok(response.json().await?)
+    }
+
+    pub async fn create<B, T>(&self, body: &B) -> Result<T, CapServiceError>
+    where
+        B: Serialize + ?Sized,
+        T: DeserializeOwned,
+    {
+        let request = self.service.authed_post(&self.path).await?.json(body);
+        let response = check(request.send().await?, Capability::Create).await?;
+        Ok(response.json().await?)
+    }
+
+    pub async fn update<B>(
+        &self,
+        id: &[&(dyn Display + Sync)],
+        body: &B,
+    ) -> Result<(), CapServiceError>
+    where
+        B: Serialize + ?Sized,
+    {
+        let request = self
+            .service
+            .authed_put(&self.item_url(id)?)
+            .await?
+            .json(body);
+        check(request.send().await?, Capability::Update).await?;
+        Ok(())
+    }
+
+    pub async fn update_all<B>(&self, body: &B) -> Result<(), CapServiceError>
+    where
+        B: Serialize + ?Sized,
+    {
+        let request = self.service.authed_put(&self.path).await?.json(body);
+        check(request.send().await?, Capability::UpdateAll).await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: &[&(dyn Display + Sync)]) -> Result<(), CapServiceError> {
+        let response = self
+            .service
+            .authed_delete(&self.item_url(id)?)
+            .await?
+            .send()
+            .await?;
+        check(response, Capability::Delete).await?;
+        Ok(())
+    }
+
+    pub async fn delete_all<B>(&self, body: &B) -> Result<(), CapServiceError>
+    where
+        B: Serialize + ?Sized,
+    {
+        let request = self.service.authed_delete(&self.path).await?.json(body);
+        check(request.send().await?, Capability::DeleteAll).await?;
+        Ok(())
+    }
+}
+
+/// Turns the error responses of a capability server back into a `CapServiceError`.
+async fn check(response: Response, required: Capability) -> Result<Response, CapServiceError> {
+    match response.status() {
+        StatusCode::FORBIDDEN => match response.json::<DeniedBody>().await {
+            Ok(denied) => Err(CapServiceError::Denied {
+                required: denied.required,
+                presented: denied.presented,
+            }),
+            Err(_) => Err(CapServiceError::Denied {
+                required,
+                presented: Capability::Invalid,
+            }),
+        },
+        StatusCode::NOT_FOUND => Err(CapServiceError::NotFound),
+        _ => Ok(response.error_for_status()?),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ServiceBackend, WebServiceConfig};
+
+    #[tokio::test]
+    async fn percent_encode_id_segments() {
+        let config = WebServiceConfig::new().base_url("http://localhost:8080/api");
+        let service = WebService::build(config).await.unwrap();
+        let client = RemoteClient::new(service, "/orders/");
+        assert_eq!(
+            client.item_url(&[&"a/b?c#d"]).unwrap(),
+            "http://localhost:8080/api/orders/a%2Fb%3Fc%23d"
+        );
+        assert_eq!(
+            client.item_url(&[&1, &"x y"]).unwrap(),
+            "http://localhost:8080/api/orders/1/x%20y"
+        );
+        assert!(matches!(
+            client.item_url(&[&".."]),
+            Err(CapServiceError::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,206 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+
+use crate::client::DeniedBody;
+use crate::Capability;
+
+/// The error returned by every generated `CapService` and capability function.
+#[derive(Debug)]
+pub enum CapServiceError {
+    /// The presented capability does not grant the operation.
+    Denied {
+        required: Capability,
+        presented: Capability,
+    },
+    NotFound,
+    /// The lease of the presented capability ran out, see `capabilities::Lease`.
+    Expired,
+    Backend(BackendError),
+    Config(String),
+}
+
+#[derive(Debug)]
+pub enum BackendError {
+    Sqlx(sqlx::Error),
+    Migrate(sqlx::migrate::MigrateError),
+    Reqwest(reqwest::Error),
+    Other(Box<dyn StdError + Send + Sync>),
+}
+
+impl CapServiceError {
+    /// Wraps the error of a `ServiceBackend`, keeping sqlx and reqwest errors typed.
+    pub fn from_backend<E>(error: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        let error: Box<dyn StdError + Send + Sync> = Box::new(error);
+        let error = match error.downcast::<CapServiceError>() {
+            Ok(e) => return *e,
+            Err(e) => e,
+        };
+        let error = match error.downcast::<sqlx::Error>() {
+            Ok(e) => return CapServiceError::from(*e),
+            Err(e) => e,
+        };
+        match error.downcast::<reqwest::Error>() {
+            Ok(e) => CapServiceError::from(*e),
+            Err(e) => CapServiceError::Backend(BackendError::Other(e)),
+        }
+    }
+}
+
+impl fmt::Display for CapServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CapServiceError::Denied {
+                required,
+                presented,
+            } => write!(
+                f,
+                "Capability {:?} required, {:?} presented",
+                required, presented
+            ),
+            CapServiceError::NotFound => write!(f, "Not found"),
+            CapServiceError::Expired => write!(f, "Capability expired"),
+            CapServiceError::Backend(e) => write!(f, "Backend error: {}", e),
+            CapServiceError::Config(e) => write!(f, "Configuration error: {}", e),
+        }
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Sqlx(e) => write!(f, "{}", e),
+            BackendError::Migrate(e) => write!(f, "{}", e),
+            BackendError::Reqwest(e) => write!(f, "{}", e),
+            BackendError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl StdError for CapServiceError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            CapServiceError::Backend(BackendError::Sqlx(e)) => Some(e),
+            CapServiceError::Backend(BackendError::Migrate(e)) => Some(e),
+            CapServiceError::Backend(BackendError::Reqwest(e)) => Some(e),
+            CapServiceError::Backend(BackendError::Other(e)) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for CapServiceError {
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::RowNotFound => CapServiceError::NotFound,
+            sqlx::Error::Configuration(e) => CapServiceError::Config(e.to_string()),
+            e => CapServiceError::Backend(BackendError::Sqlx(e)),
+        }
+    }
+}
+
+impl From<sqlx::migrate::MigrateError> for CapServiceError {
+    fn from(error: sqlx::migrate::MigrateError) -> Self {
+        CapServiceError::Backend(BackendError::Migrate(error))
+    }
+}
+
+impl From<std::io::Error> for CapServiceError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => CapServiceError::NotFound,
+            _ => CapServiceError::Backend(BackendError::Other(Box::new(error))),
+        }
+    }
+}
+
+impl From<reqwest::Error> for CapServiceError {
+    fn from(error: reqwest::Error) -> Self {
+        match error.status() {
+            Some(StatusCode::NOT_FOUND) => CapServiceError::NotFound,
+            _ => CapServiceError::Backend(BackendError::Reqwest(error)),
+        }
+    }
+}
+
+impl ResponseError for CapServiceError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            CapServiceError::Denied { .. } => StatusCode::FORBIDDEN,
+            CapServiceError::NotFound => StatusCode::NOT_FOUND,
+            CapServiceError::Expired => StatusCode::UNAUTHORIZED,
+            CapServiceError::Backend(BackendError::Reqwest(_)) => StatusCode::BAD_GATEWAY,
+            CapServiceError::Backend(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            CapServiceError::Config(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // Clients read the capabilities back from the body, see `RemoteClient`.
+        if let CapServiceError::Denied {
+            required,
+            presented,
+        } = self
+        {
+            return HttpResponse::build(self.status_code()).json(DeniedBody {
+                required: *required,
+                presented: *presented,
+            });
+        }
+        // Backend and configuration errors are logged, not handed to the caller.
+        let body = match self {
+            CapServiceError::Backend(_) | CapServiceError::Config(_) => {
+                log::error!("{}", self);
+                self.status_code()
+                    .canonical_reason()
+                    .unwrap_or_default()
+                    .to_string()
+            }
+            _ => self.to_string(),
+        };
+        HttpResponse::build(self.status_code()).body(body)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn denied_is_forbidden() {
+        let error = CapServiceError::Denied {
+            required: Capability::Delete,
+            presented: Capability::Read,
+        };
+        assert_eq!(error.status_code(), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn missing_row_is_not_found() {
+        let error = CapServiceError::from(sqlx::Error::RowNotFound);
+        assert_eq!(error.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn backend_errors_keep_their_type() {
+        let error = CapServiceError::from_backend(sqlx::Error::PoolTimedOut);
+        assert!(matches!(
+            error,
+            CapServiceError::Backend(BackendError::Sqlx(sqlx::Error::PoolTimedOut))
+        ));
+
+        let error = CapServiceError::from_backend(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "bucket missing",
+        ));
+        assert!(matches!(
+            error,
+            CapServiceError::Backend(BackendError::Other(_))
+        ));
+    }
+}
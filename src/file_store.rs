@@ -0,0 +1,297 @@
+use std::fmt::Display;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::fs;
+
+use crate::{CapServiceError, ServiceBackend};
+
+/// Points at one document of a `FileStore`, implemented by the struct and its id struct.
+pub trait Locator {
+    type Document: Document;
+
+    fn key(&self) -> String;
+
+    /// The whole document, `None` when only the id is known.
+    fn document(&self) -> Option<&Self::Document> {
+        None
+    }
+}
+
+/// Implemented by `#[capabilities(..., id = "field", collection = "name")]` structs.
+pub trait Document:
+    Locator<Document = Self> + Serialize + DeserializeOwned + Send + Sync + 'static
+{
+    /// The directory below the root of the store that keeps these documents.
+    const COLLECTION: &'static str;
+}
+
+/// The key of a document with a composite id, its parts joined by `_`. `%` and `_` inside the
+/// parts are percent-encoded so that `("a_b", "c")` and `("a", "b_c")` keep apart. A single part
+/// is its own key.
+pub fn composite_key(parts: &[&dyn Display]) -> String {
+    if let [part] = parts {
+        return part.to_string();
+    }
+    parts
+        .iter()
+        .map(|part| part.to_string().replace('%', "%25").replace('_', "%5F"))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// The `FileStore` backend, keeps documents as `<root>/<collection>/<key>.json`.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    root: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileStore { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn collection<T: Document>(&self) -> PathBuf {
+        self.root.join(T::COLLECTION)
+    }
+
+    fn path<L: Locator>(&self, locator: &L) -> Result<PathBuf, CapServiceError> {
+        let key = locator.key();
+        // The key ends up in a path, it must not leave the collection.
+        if key.is_empty() || key == "." || key == ".." || key.contains(['/', '\\']) {
+            return Err(CapServiceError::Config(format!(
+                "{:?} cannot be used as a document key",
+                key
+            )));
+        }
+        Ok(self
+            .collection::<L::Document>()
+            .join(format!("{}.json", key)))
+    }
+
+    async fn write<T: Document>(&self, path: &Path, document: &T) -> Result<(), CapServiceError> {
+        let json = serde_json::to_vec_pretty(document).map_err(CapServiceError::from_backend)?;
+        // Write next to the document and rename, so that readers never see half a document.
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json).await?;
+        fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+
+    pub async fn create<T: Document>(&self, document: T) -> Result<T, CapServiceError> {
+        let path = self.path(&document)?;
+        fs::create_dir_all(self.collection::<T>()).await?;
+        if fs::metadata(&path).await.is_ok() {
+            let message = format!("{} {} exists already", T::COLLECTION, document.key());
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, message).into());
+        }
+        self.write(&path, &document).await?;
+        Ok(document)
+    }
+
+    pub async fn read<L: Locator>(&self, locator: &L) -> Result<L::Document, CapServiceError> {
+        let json = fs::read(self.path(locator)?).await?;
+        serde_json::from_slice(&json).map_err(CapServiceError::from_backend)
+    }
+
+    /// All documents of the collection, ordered by their key.
+    pub async fn read_all<T: Document>(&self) -> Result<Vec<T>, CapServiceError> {
+        let mut entries = match fs::read_dir(self.collection::<T>()).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut paths = vec![];
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut documents = vec![];
+        for path in paths {
+            let json = fs::read(path).await?;
+            documents.push(serde_json::from_slice(&json).map_err(CapServiceError::from_backend)?);
+        }
+        Ok(documents)
+    }
+
+    pub async fn update<L: Locator>(&self, locator: &L) -> Result<(), CapServiceError> {
+        let document = locator.document().ok_or_else(|| {
+            CapServiceError::Config(format!(
+                "Updating {} {} needs the document, not only its id",
+                L::Document::COLLECTION,
+                locator.key()
+            ))
+        })?;
+        let path = self.path(locator)?;
+        fs::metadata(&path).await?;
+        self.write(&path, document).await
+    }
+
+    pub async fn delete<L: Locator>(&self, locator: &L) -> Result<(), CapServiceError> {
+        fs::remove_file(self.path(locator)?).await?;
+        Ok(())
+    }
+
+    /// Creates the documents in order. The batch is created as a whole or not at all, if one
+    /// of them fails the ones created before it are removed again.
+    pub async fn create_all<T: Document>(
+        &self,
+        documents: Vec<T>,
+    ) -> Result<Vec<T>, CapServiceError> {
+        let mut created = vec![];
+        for document in documents {
+            match self.create(document).await {
+                Ok(document) => created.push(document),
+                Err(e) => {
+                    for document in &created {
+                        let _ = self.delete(document).await;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(created)
+    }
+
+    pub async fn update_all<T: Document>(&self, documents: &[T]) -> Result<(), CapServiceError> {
+        for document in documents {
+            self.update(document).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn delete_all<T: Document>(&self, documents: &[T]) -> Result<(), CapServiceError> {
+        for document in documents {
+            self.delete(document).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ServiceBackend for FileStore {
+    /// The root directory of the store.
+    type Config = PathBuf;
+    type Error = CapServiceError;
+
+    async fn build(conf: Self::Config) -> Result<Self, Self::Error> {
+        fs::create_dir_all(&conf).await?;
+        Ok(FileStore::new(conf))
+    }
+
+    async fn health(&self) -> Result<(), Self::Error> {
+        if fs::metadata(&self.root).await?.is_dir() {
+            Ok(())
+        } else {
+            Err(CapServiceError::Config(format!(
+                "{} is not a directory",
+                self.root.display()
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Note {
+        id: String,
+        text: String,
+    }
+
+    impl Locator for Note {
+        type Document = Note;
+
+        fn key(&self) -> String {
+            self.id.clone()
+        }
+
+        fn document(&self) -> Option<&Note> {
+            Some(self)
+        }
+    }
+
+    impl Document for Note {
+        const COLLECTION: &'static str = "notes";
+    }
+
+    #[tokio::test]
+    async fn keep_documents_by_key() {
+        let root = std::env::temp_dir().join("capabilities_file_store");
+        let _ = std::fs::remove_dir_all(&root);
+        let store = FileStore::build(root.clone()).await.unwrap();
+
+        let note = Note {
+            id: "a".to_string(),
+            text: "first".to_string(),
+        };
+        store.create(note).await.unwrap();
+        assert!(root.join("notes").join("a.json").is_file());
+
+        let evil = Note {
+            id: "../a".to_string(),
+            text: "escape".to_string(),
+        };
+        assert!(matches!(
+            store.create(evil).await,
+            Err(CapServiceError::Config(_))
+        ));
+
+        let notes: Vec<Note> = store.read_all().await.unwrap();
+        assert_eq!(notes.len(), 1);
+        store.delete(&notes[0]).await.unwrap();
+        assert!(matches!(
+            store.read(&notes[0]).await,
+            Err(CapServiceError::NotFound)
+        ));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn keep_composite_keys_apart() {
+        assert_eq!(composite_key(&[&"a_b"]), "a_b");
+        assert_eq!(composite_key(&[&"a", &1]), "a_1");
+        assert_ne!(
+            composite_key(&[&"a_b", &"c"]),
+            composite_key(&[&"a", &"b_c"])
+        );
+        assert_ne!(
+            composite_key(&[&"a%5Fb", &"c"]),
+            composite_key(&[&"a_b", &"c"])
+        );
+    }
+
+    #[tokio::test]
+    async fn create_batch_as_a_whole() {
+        let root = std::env::temp_dir().join("capabilities_file_store_batch");
+        let _ = std::fs::remove_dir_all(&root);
+        let store = FileStore::build(root.clone()).await.unwrap();
+        let note = |id: &str| Note {
+            id: id.to_string(),
+            text: "batch".to_string(),
+        };
+
+        store.create(note("b")).await.unwrap();
+        let batch = vec![note("a"), note("b"), note("c")];
+        assert!(store.create_all(batch).await.is_err());
+        let notes: Vec<Note> = store.read_all().await.unwrap();
+        assert_eq!(notes, vec![note("b")]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
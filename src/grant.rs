@@ -0,0 +1,197 @@
+use std::collections::BTreeSet;
+
+use actix_web::dev::Payload;
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest};
+use futures_util::future::{ready, Ready};
+use gnap_cli::models::access_token::AccessRequest;
+
+use crate::{CapServiceError, Capability, Page};
+
+/// The prefix of the actions granting single fields, like `update:orders.status`.
+const UPDATE_FIELD: &str = "update:";
+/// The prefix of the actions revealing sensitive fields, like `read:person.personnummer`.
+const READ_FIELD: &str = "read:";
+
+/// A presented capability with the fields it grants, for patches checking field by field and
+/// projections leaving out the sensitive fields.
+///
+/// `Capability::Update` grants every field, `Capability::UpdateFields` the fields named by the
+/// token. The fields are kept as the actions naming them, like `update:orders.status`. A
+/// `Capability` converts into a grant without fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    pub capability: Capability,
+    pub fields: BTreeSet<String>,
+}
+
+impl Grant {
+    pub fn new(capability: Capability) -> Self {
+        Grant {
+            capability,
+            fields: BTreeSet::new(),
+        }
+    }
+
+    /// Grants `update:resource.field`.
+    pub fn with_field(mut self, resource: &str, field: &str) -> Self {
+        self.fields
+            .insert(field_action(UPDATE_FIELD, resource, field));
+        self
+    }
+
+    /// Grants `read:resource.field`, revealing a sensitive field.
+    pub fn with_read_field(mut self, resource: &str, field: &str) -> Self {
+        self.fields
+            .insert(field_action(READ_FIELD, resource, field));
+        self
+    }
+
+    /// Whether the field may be changed, `grantable` fields also by a grant of their own.
+    pub fn allows_field(&self, resource: &str, field: &str, grantable: bool) -> bool {
+        match self.capability {
+            Capability::Update => true,
+            Capability::UpdateFields if grantable => {
+                self.fields
+                    .contains(&field_action(UPDATE_FIELD, resource, field))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a projection may show the sensitive field, whatever the capability.
+    pub fn allows_read(&self, resource: &str, field: &str) -> bool {
+        self.fields
+            .contains(&field_action(READ_FIELD, resource, field))
+    }
+
+    /// Denies a patch changing `field` unless `allows_field`.
+    pub fn check_field(
+        &self,
+        resource: &str,
+        field: &str,
+        grantable: bool,
+    ) -> Result<(), CapServiceError> {
+        match self.allows_field(resource, field, grantable) {
+            true => Ok(()),
+            false => Err(CapServiceError::Denied {
+                required: Capability::Update,
+                presented: self.capability,
+            }),
+        }
+    }
+
+    /// The fields of the `update:resource.field` and `read:resource.field` actions of a token.
+    pub(crate) fn from_access(capability: Capability, access_list: &[AccessRequest]) -> Self {
+        let mut grant = Grant::new(capability);
+        for access in access_list {
+            if let AccessRequest::Value {
+                actions: Some(actions),
+                ..
+            } = access
+            {
+                let fields = actions
+                    .iter()
+                    .filter(|a| is_field_action(a) || a.starts_with(READ_FIELD));
+                grant.fields.extend(fields.cloned());
+            }
+        }
+        grant
+    }
+}
+
+impl From<Capability> for Grant {
+    fn from(capability: Capability) -> Self {
+        Grant::new(capability)
+    }
+}
+
+/// The grant `token_introspection` put in the request, or else its `Capability` without fields.
+impl FromRequest for Grant {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let extensions = req.extensions();
+        let grant = match extensions.get::<Grant>() {
+            Some(grant) => Ok(grant.clone()),
+            None => extensions
+                .get::<Capability>()
+                .map(|c| Grant::new(*c))
+                .ok_or_else(|| actix_web::error::ErrorForbidden("No capability presented")),
+        };
+        ready(grant)
+    }
+}
+
+/// Whether `action` grants a single field, like `update:orders.status`.
+pub(crate) fn is_field_action(action: &str) -> bool {
+    action.starts_with(UPDATE_FIELD)
+}
+
+fn field_action(prefix: &str, resource: &str, field: &str) -> String {
+    format!("{}{}.{}", prefix, resource, field)
+}
+
+/// What a `Read`, `ReadAll` or `StreamAll` returns of an item, see `#[cap(sensitive)]`.
+///
+/// `#[capabilities]` implements it for every item. Without sensitive fields the view is the
+/// item itself, with them it is `XView`, where a sensitive field is `None` unless the grant
+/// has `read:resource.field`.
+pub trait Projection {
+    type View;
+
+    fn project(self, grant: &Grant) -> Self::View;
+}
+
+/// Operations handing out nothing, like `Update` and `Delete`.
+impl Projection for () {
+    type View = ();
+
+    fn project(self, _grant: &Grant) -> Self::View {}
+}
+
+impl<T: Projection> Projection for Vec<T> {
+    type View = Vec<T::View>;
+
+    fn project(self, grant: &Grant) -> Self::View {
+        self.into_iter().map(|item| item.project(grant)).collect()
+    }
+}
+
+impl<T: Projection> Projection for Page<T> {
+    type View = Page<T::View>;
+
+    fn project(self, grant: &Grant) -> Self::View {
+        Page {
+            items: self.items.project(grant),
+            total: self.total,
+            next: self.next,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn grant_single_fields() {
+        let grant = Grant::new(Capability::UpdateFields).with_field("orders", "status");
+        assert!(grant.allows_field("orders", "status", true));
+        assert!(!grant.allows_field("orders", "status", false));
+        assert!(!grant.allows_field("orders", "amount", true));
+        assert!(grant.check_field("orders", "amount", true).is_err());
+
+        let grant = Grant::from(Capability::Update);
+        assert!(grant.allows_field("orders", "amount", false));
+        assert!(!Grant::from(Capability::Read).allows_field("orders", "status", true));
+    }
+
+    #[test]
+    fn grant_sensitive_reads() {
+        let grant = Grant::new(Capability::Read).with_read_field("person", "personnummer");
+        assert!(grant.allows_read("person", "personnummer"));
+        assert!(!grant.allows_read("person", "email"));
+        assert!(!Grant::from(Capability::Update).allows_read("person", "personnummer"));
+    }
+}
@@ -0,0 +1,55 @@
+use actix_web::{web, HttpResponse, Route};
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::CapServiceError;
+
+/// The health of one backend of a `CapService`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendHealth {
+    pub name: String,
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BackendHealth {
+    pub fn new(name: impl Into<String>, health: Result<(), CapServiceError>) -> Self {
+        BackendHealth {
+            name: name.into(),
+            healthy: health.is_ok(),
+            error: health.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// Implemented by every generated `CapService`, see `readiness_route`.
+#[async_trait]
+pub trait Readiness: Send + Sync + 'static {
+    /// Checks every backend of the service.
+    async fn backends(&self) -> Vec<BackendHealth>;
+}
+
+#[derive(Debug, Serialize)]
+struct ReadinessReport {
+    ready: bool,
+    backends: Vec<BackendHealth>,
+}
+
+/// Answers 200 when every backend of the service is healthy and 503 otherwise,
+/// reporting each backend as JSON.
+pub async fn readiness<S: Readiness>(service: web::Data<S>) -> HttpResponse {
+    let backends = service.backends().await;
+    let ready = backends.iter().all(|b| b.healthy);
+    let mut response = if ready {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    };
+    response.json(ReadinessReport { ready, backends })
+}
+
+/// `.route("/ready", readiness_route::<CapService>())`, the service has to be in the app data.
+pub fn readiness_route<S: Readiness>() -> Route {
+    web::get().to(readiness::<S>)
+}
@@ -1,16 +1,54 @@
 pub extern crate capabilities_derive;
 
+mod backend;
+mod channel;
+mod client;
+mod error;
+mod file_store;
+mod grant;
+mod health;
+mod mock;
+mod pool;
+mod query;
+mod route;
+mod schema;
+mod stream;
+mod token;
+mod transaction;
+mod verb;
+mod web;
+
 pub use ::capabilities_derive::capability;
 pub use ::capabilities_derive::service;
 use actix_web::dev::ServiceRequest;
 use actix_web_httpauth::extractors::bearer::BearerAuth;
+pub use backend::ServiceBackend;
+pub use channel::ChannelService;
+pub use client::RemoteClient;
+pub use error::{BackendError, CapServiceError};
+pub use file_store::{composite_key, Document, FileStore, Locator};
+pub use grant::{Grant, Projection};
+pub use health::{readiness, readiness_route, BackendHealth, Readiness};
+pub use mock::{Invocation, MockService, Observe};
+pub use pool::{FromPoolConfig, PoolBuilder, PoolConfig};
+pub use query::{
+    FilterValue, Page, Query, QueryDatabase, QueryField, QueryFilter, Sort, SortOrder,
+};
+pub use reqwest::Url;
+pub use route::{Route, Routing};
+pub use schema::{dump_schemas, Dialect, Schema, TableSchema};
+pub use stream::{fetch_stream, CapStream, Lease, NdJson, STREAM_BUFFER};
+pub use token::{AccessToken, BearerToken, CachedToken, GnapGrant, TokenProvider, TokenSource};
+pub use transaction::{DbConnection, PoolDatabase, TxSlot};
+pub use verb::{register_verb, Verb};
+pub use web::{WebService, WebServiceConfig};
 
-use reqwest::Client;
 use sqlx::pool::Pool;
 use sqlx::sqlite::Sqlite;
+use sqlx::MySql;
 use sqlx::Postgres;
 
-use actix_web::dev::Payload;
+use actix_web::dev::{Extensions, Payload};
 use actix_web::HttpMessage;
 use actix_web::{Error, FromRequest, HttpRequest, Result};
 use futures_util::future::{ok, Ready};
@@ -18,6 +56,8 @@ use futures_util::future::{ok, Ready};
 use gnap_cli::models::access_token::AccessRequest;
 use gnap_cli::GnapClient;
 use log::debug;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
 
 #[allow(dead_code)]
 pub struct Create<T> {
@@ -37,9 +77,16 @@ pub struct Delete<T> {
     pub data: T,
 }
 
+pub struct CreateAll<T> {
+    pub data: T,
+}
 pub struct ReadAll<T> {
     pub data: T,
 }
+/// `ReadAll` one item at a time, performing it requires `Capability::ReadAll`.
+pub struct StreamAll<T> {
+    pub data: PhantomData<T>,
+}
 pub struct UpdateAll<T> {
     pub data: T,
 }
@@ -47,24 +94,64 @@ pub struct DeleteAll<T> {
     pub data: T,
 }
 
+pub struct Publish<T> {
+    pub data: T,
+}
+pub struct Consume<T> {
+    pub data: T,
+}
+
 pub type SqliteDb = Pool<Sqlite>;
 pub type PostgresDb = Pool<Postgres>;
-pub type WebService = Client;
+pub type MySqlDb = Pool<MySql>;
 pub struct EmptyInput;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Capability {
     Read,
     ReadAll,
     Write,
     Create,
+    CreateAll,
     Update,
     UpdateAll,
+    /// Update of the fields named by `update:resource.field` actions, see `Grant`.
+    UpdateFields,
     Delete,
     DeleteAll,
+    Publish,
+    Consume,
+    /// A verb declared with `#[capabilities(..., Approve(input = ..., output = ...))]`.
+    Custom(Verb),
     Invalid,
 }
 
+/// Proof that the presented capability is the one an operation requires. Backends performing
+/// the operations themselves, like `ChannelService`, take it instead of trusting their caller.
+#[derive(Debug)]
+pub struct Authorized(Capability);
+
+impl Authorized {
+    /// The check of every capability function.
+    pub fn check(required: Capability, presented: Capability) -> Result<Self, CapServiceError> {
+        match required == presented {
+            true => Ok(Authorized(required)),
+            false => Err(CapServiceError::Denied {
+                required,
+                presented,
+            }),
+        }
+    }
+
+    pub fn capability(&self) -> Capability {
+        self.0
+    }
+
+    pub(crate) fn require(&self, required: Capability) -> Result<(), CapServiceError> {
+        Authorized::check(required, self.0).map(|_| ())
+    }
+}
+
 impl FromRequest for Capability {
     type Error = Error;
     type Future = Ready<Result<Self, Self::Error>>;
@@ -106,7 +193,16 @@ pub async fn token_introspection(
                         Ok(cap) => cap.first().unwrap().to_owned(),
                         Err(_) => Capability::Invalid,
                     };
-                    req.extensions_mut().insert(cap);
+                    // `exp` counts the seconds since the Unix epoch.
+                    let expires_at = ir.exp.map(|exp| u64::try_from(exp).unwrap_or_default());
+                    insert_token_extensions(
+                        &mut req.extensions_mut(),
+                        cap,
+                        &access_req,
+                        expires_at,
+                    );
+                    req.extensions_mut()
+                        .insert(BearerToken(header.token().to_string()));
                     debug!("{:#?}", req);
                     Ok(req)
                 }
@@ -124,23 +220,31 @@ pub async fn token_introspection(
     }
 }
 
+/// Puts what a token grants into the request, for the `Capability`, `Grant` and `Lease`
+/// extractors. The lease expires with the token, `expires_at` seconds after the Unix epoch.
+fn insert_token_extensions(
+    extensions: &mut Extensions,
+    cap: Capability,
+    access_list: &[AccessRequest],
+    expires_at: Option<u64>,
+) {
+    let grant = Grant::from_access(cap, access_list);
+    let lease = match expires_at {
+        Some(expires_at) => Lease::new(grant.clone()).expires_at_unix(expires_at),
+        None => Lease::new(grant.clone()),
+    };
+    extensions.insert(cap);
+    extensions.insert(grant);
+    extensions.insert(lease);
+}
+
 fn get_access_type(access_list: &Vec<AccessRequest>) -> Result<Vec<Capability>, Error> {
     let mut caps = vec![];
     for access in access_list {
         match access {
             AccessRequest::Value { actions, .. } => {
                 for action in actions.clone().unwrap() {
-                    match action.as_str() {
-                        "read" => caps.push(Capability::Read),
-                        "create" => caps.push(Capability::Create),
-                        "write" => caps.push(Capability::Write),
-                        "update" => caps.push(Capability::Update),
-                        "delete" => caps.push(Capability::Delete),
-                        "readall" => caps.push(Capability::ReadAll),
-                        "deleteall" => caps.push(Capability::DeleteAll),
-                        "updateall" => caps.push(Capability::UpdateAll),
-                        _ => caps.push(Capability::Invalid),
-                    }
+                    caps.push(get_capability(&action));
                 }
             }
             _ => return Err(actix_web::error::ErrorForbidden("Unknown access type")),
@@ -149,6 +253,27 @@ fn get_access_type(access_list: &Vec<AccessRequest>) -> Result<Vec<Capability>,
     Ok(caps)
 }
 
+fn get_capability(action: &str) -> Capability {
+    match action {
+        "read" => Capability::Read,
+        "create" => Capability::Create,
+        "write" => Capability::Write,
+        "update" => Capability::Update,
+        "delete" => Capability::Delete,
+        "createall" => Capability::CreateAll,
+        "readall" => Capability::ReadAll,
+        "deleteall" => Capability::DeleteAll,
+        "updateall" => Capability::UpdateAll,
+        "publish" => Capability::Publish,
+        "consume" => Capability::Consume,
+        field if grant::is_field_action(field) => Capability::UpdateFields,
+        custom => match Verb::lookup(custom) {
+            Some(verb) => Capability::Custom(verb),
+            None => Capability::Invalid,
+        },
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -179,4 +304,47 @@ mod test {
         assert_eq!(c, Capability::Read);
         assert_ne!(c, Capability::Delete);
     }
+
+    #[actix_web::test]
+    async fn lease_expires_with_token() {
+        let req = actix_web::test::TestRequest::default().to_srv_request();
+        insert_token_extensions(&mut req.extensions_mut(), Capability::ReadAll, &[], Some(0));
+        let lease = Lease::extract(req.request()).await.unwrap();
+        assert_eq!(lease.capability(), Capability::ReadAll);
+        assert!(lease.is_expired());
+
+        let req = actix_web::test::TestRequest::default().to_srv_request();
+        let exp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600;
+        insert_token_extensions(
+            &mut req.extensions_mut(),
+            Capability::ReadAll,
+            &[],
+            Some(exp),
+        );
+        let lease = Lease::extract(req.request()).await.unwrap();
+        assert!(lease.expires_at.is_some());
+        assert!(!lease.is_expired());
+        let grant = Grant::extract(req.request()).await.unwrap();
+        assert_eq!(grant.capability, Capability::ReadAll);
+        assert_eq!(lease.grant, grant);
+    }
+
+    #[test]
+    fn map_registered_verbs() {
+        // The registry is shared by every test, so the verb is one only this test registers.
+        let name = "map_registered_verbs_approve";
+        assert_eq!(get_capability(name), Capability::Invalid);
+        let approve = register_verb(name);
+        assert_eq!(get_capability(name), Capability::Custom(approve));
+        assert_eq!(get_capability("read"), Capability::Read);
+        assert_eq!(get_capability("createall"), Capability::CreateAll);
+        assert_eq!(
+            get_capability("update:orders.status"),
+            Capability::UpdateFields
+        );
+    }
 }
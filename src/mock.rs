@@ -0,0 +1,250 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+
+use crate::{CapServiceError, Capability, ServiceBackend};
+
+/// Lets a service watch the capability functions called on it.
+///
+/// Every generated `CapService` implements it, but only the capability functions of a `MockService`
+/// call `observe` and `scripted`.
+pub trait Observe {
+    /// Called by a capability function before it checks the presented capability.
+    fn observe<Operation: 'static>(
+        &self,
+        _operation: &Operation,
+        _required: Capability,
+        _presented: Capability,
+    ) {
+    }
+
+    /// Called by `CapabilityTrait::perform`, whether or not a capability function led there.
+    fn performing<Operation: 'static>(&self, _operation: &Operation, _required: Capability) {}
+
+    /// A result returned instead of performing the granted operation.
+    fn scripted<Operation: 'static, Data: 'static>(
+        &self,
+        _operation: &Operation,
+    ) -> Option<Result<Data, CapServiceError>> {
+        None
+    }
+}
+
+/// One call of a capability function seen by a `MockService`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    /// The operation wrapper, like `capabilities::Delete<OrdersId>`.
+    pub operation: &'static str,
+    pub operation_id: TypeId,
+    pub required: Capability,
+    /// `None` when `perform` was called directly, bypassing the capability function.
+    pub presented: Option<Capability>,
+    /// Whether the operation was performed or answered by a scripted result.
+    pub performed: bool,
+}
+
+#[derive(Default)]
+struct MockState {
+    invocations: Vec<Invocation>,
+    scripts: HashMap<TypeId, VecDeque<Box<dyn Any + Send>>>,
+}
+
+/// A backend without storage for unit-testing capability functions.
+///
+/// It records every invocation and hands out scripted results. Operations without a script
+/// run the body of the capability function.
+#[derive(Clone, Default)]
+pub struct MockService {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockService {
+    pub fn new() -> Self {
+        MockService::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        // A failed assertion in one test must not poison the mock for the others.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queues the result of the next granted `Operation`, e.g. `Read<OrdersId>`.
+    pub fn script<Operation, Data>(&self, result: Result<Data, CapServiceError>)
+    where
+        Operation: 'static,
+        Data: Send + 'static,
+    {
+        self.state()
+            .scripts
+            .entry(TypeId::of::<Operation>())
+            .or_default()
+            .push_back(Box::new(result));
+    }
+
+    pub fn invocations(&self) -> Vec<Invocation> {
+        self.state().invocations.clone()
+    }
+
+    pub fn invocations_of<Operation: 'static>(&self) -> Vec<Invocation> {
+        self.state()
+            .invocations
+            .iter()
+            .filter(|i| i.operation_id == TypeId::of::<Operation>())
+            .cloned()
+            .collect()
+    }
+
+    /// How often `Operation` was granted and performed.
+    pub fn performed<Operation: 'static>(&self) -> usize {
+        self.invocations_of::<Operation>()
+            .iter()
+            .filter(|i| i.performed)
+            .count()
+    }
+
+    /// How often `Operation` was denied.
+    pub fn denied<Operation: 'static>(&self) -> usize {
+        self.invocations_of::<Operation>()
+            .iter()
+            .filter(|i| i.presented.is_some() && i.presented != Some(i.required))
+            .count()
+    }
+
+    /// Panics if an operation requiring `required` was performed with another capability.
+    pub fn assert_never_performed_without(&self, required: Capability) {
+        let state = self.state();
+        let offending: Vec<&Invocation> = state
+            .invocations
+            .iter()
+            .filter(|i| i.required == required && i.presented != Some(required) && i.performed)
+            .collect();
+        assert!(
+            offending.is_empty(),
+            "{:?} was performed without a {:?} capability: {:?}",
+            required,
+            required,
+            offending
+        );
+    }
+
+    /// Marks the last pending, granted invocation of `Operation` as performed.
+    fn mark_performed<Operation: 'static>(&self) -> bool {
+        let mut state = self.state();
+        let pending = state.invocations.iter_mut().rev().find(|i| {
+            i.operation_id == TypeId::of::<Operation>()
+                && i.presented == Some(i.required)
+                && !i.performed
+        });
+        match pending {
+            Some(invocation) => {
+                invocation.performed = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn reset(&self) {
+        let mut state = self.state();
+        state.invocations.clear();
+        state.scripts.clear();
+    }
+}
+
+impl Observe for MockService {
+    fn observe<Operation: 'static>(
+        &self,
+        _operation: &Operation,
+        required: Capability,
+        presented: Capability,
+    ) {
+        self.state().invocations.push(Invocation {
+            operation: type_name::<Operation>(),
+            operation_id: TypeId::of::<Operation>(),
+            required,
+            presented: Some(presented),
+            performed: false,
+        });
+    }
+
+    fn performing<Operation: 'static>(&self, _operation: &Operation, required: Capability) {
+        if !self.mark_performed::<Operation>() {
+            self.state().invocations.push(Invocation {
+                operation: type_name::<Operation>(),
+                operation_id: TypeId::of::<Operation>(),
+                required,
+                presented: None,
+                performed: true,
+            });
+        }
+    }
+
+    fn scripted<Operation: 'static, Data: 'static>(
+        &self,
+        _operation: &Operation,
+    ) -> Option<Result<Data, CapServiceError>> {
+        let script = self
+            .state()
+            .scripts
+            .get_mut(&TypeId::of::<Operation>())?
+            .pop_front()?;
+        match script.downcast::<Result<Data, CapServiceError>>() {
+            Ok(result) => {
+                self.mark_performed::<Operation>();
+                Some(*result)
+            }
+            Err(_) => panic!(
+                "Scripted result for {} is not a Result<{}, CapServiceError>",
+                type_name::<Operation>(),
+                type_name::<Data>()
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl ServiceBackend for MockService {
+    type Config = ();
+    type Error = CapServiceError;
+
+    async fn build(_conf: Self::Config) -> Result<Self, Self::Error> {
+        Ok(MockService::new())
+    }
+
+    async fn health(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Delete, Read};
+
+    #[test]
+    fn record_and_script_operations() {
+        let mock = MockService::new();
+        mock.script::<Read<i32>, String>(Ok("scripted".to_string()));
+
+        let read = Read { data: 1 };
+        mock.observe(&read, Capability::Read, Capability::Read);
+        let result: Option<Result<String, CapServiceError>> = mock.scripted(&read);
+        assert_eq!(result.unwrap().unwrap(), "scripted");
+        assert!(mock.scripted::<Read<i32>, String>(&read).is_none());
+
+        mock.observe(&Delete { data: 1 }, Capability::Delete, Capability::Read);
+        assert_eq!(mock.performed::<Read<i32>>(), 1);
+        assert_eq!(mock.denied::<Delete<i32>>(), 1);
+        mock.assert_never_performed_without(Capability::Delete);
+    }
+
+    #[test]
+    #[should_panic(expected = "Delete was performed without a Delete capability")]
+    fn catch_performs_bypassing_the_capability_check() {
+        let mock = MockService::new();
+        mock.performing(&Delete { data: 1 }, Capability::Delete);
+        mock.assert_never_performed_without(Capability::Delete);
+    }
+}
@@ -0,0 +1,195 @@
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sqlx::pool::PoolOptions;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode};
+use sqlx::Database;
+
+use crate::CapServiceError;
+
+pub const DATABASE_URL: &str = "DATABASE_URL";
+pub const DATABASE_REPLICA_URL: &str = "DATABASE_REPLICA_URL";
+pub const DEFAULT_CONNECT_RETRIES: u32 = 3;
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Connection pool settings for the database backends of `#[service]`.
+#[derive(Debug, Clone, Default)]
+pub struct PoolConfig {
+    pub url: String,
+    /// A read replica, `Read` and `ReadAll` are performed on it, see `capabilities::Route`.
+    pub replica_url: Option<String>,
+    pub max_connections: Option<u32>,
+    pub min_connections: Option<u32>,
+    pub acquire_timeout: Option<Duration>,
+    pub idle_timeout: Option<Duration>,
+    pub lazy: bool,
+    /// Only used by SQLite pools.
+    pub wal: bool,
+    /// Only used by SQLite pools, sqlx turns foreign keys on by default.
+    pub foreign_keys: Option<bool>,
+    /// How often `build` tries again when the database cannot be reached,
+    /// `DEFAULT_CONNECT_RETRIES` if not set.
+    pub connect_retries: Option<u32>,
+    /// The wait before the first retry, it doubles with every retry.
+    pub retry_backoff: Option<Duration>,
+}
+
+impl PoolConfig {
+    pub fn new(url: impl Into<String>) -> Self {
+        PoolConfig {
+            url: url.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_env() -> Result<Self, CapServiceError> {
+        let url = std::env::var(DATABASE_URL)
+            .map_err(|_| CapServiceError::Config(format!("{} is not set", DATABASE_URL)))?;
+        let mut config = PoolConfig::new(url);
+        config.replica_url = std::env::var(DATABASE_REPLICA_URL).ok();
+        Ok(config)
+    }
+
+    /// The settings of the replica pool, the same as the primary ones but for the url.
+    pub fn replica(&self) -> Option<PoolConfig> {
+        self.replica_url.as_ref().map(|url| PoolConfig {
+            url: url.clone(),
+            replica_url: None,
+            ..self.clone()
+        })
+    }
+
+    pub fn pool_options<DB: Database>(&self) -> PoolOptions<DB> {
+        let mut options = PoolOptions::<DB>::new();
+        if let Some(max) = self.max_connections {
+            options = options.max_connections(max);
+        }
+        if let Some(min) = self.min_connections {
+            options = options.min_connections(min);
+        }
+        if let Some(timeout) = self.acquire_timeout {
+            options = options.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.idle_timeout {
+            options = options.idle_timeout(timeout);
+        }
+        options
+    }
+
+    pub fn connect_retries(&self) -> u32 {
+        self.connect_retries.unwrap_or(DEFAULT_CONNECT_RETRIES)
+    }
+
+    pub fn retry_backoff(&self) -> Duration {
+        self.retry_backoff.unwrap_or(DEFAULT_RETRY_BACKOFF)
+    }
+
+    pub fn sqlite_options(&self) -> Result<SqliteConnectOptions, sqlx::Error> {
+        let mut options = SqliteConnectOptions::from_str(&self.url)?;
+        if self.wal {
+            options = options.journal_mode(SqliteJournalMode::Wal);
+        }
+        if let Some(on) = self.foreign_keys {
+            options = options.foreign_keys(on);
+        }
+        Ok(options)
+    }
+}
+
+impl From<String> for PoolConfig {
+    fn from(url: String) -> Self {
+        PoolConfig::new(url)
+    }
+}
+
+/// Implemented by the generated `CapService` of the database backends.
+#[async_trait]
+pub trait FromPoolConfig: Sized {
+    async fn from_pool_config(config: PoolConfig) -> Result<Self, CapServiceError>;
+}
+
+/// Builder behind the generated `CapServiceBuilder`.
+pub struct PoolBuilder<S> {
+    config: PoolConfig,
+    service: PhantomData<S>,
+}
+
+impl<S: FromPoolConfig> PoolBuilder<S> {
+    pub fn new() -> Self {
+        PoolBuilder {
+            config: PoolConfig::default(),
+            service: PhantomData,
+        }
+    }
+
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.config.url = url.into();
+        self
+    }
+
+    pub fn replica_url(mut self, url: impl Into<String>) -> Self {
+        self.config.replica_url = Some(url.into());
+        self
+    }
+
+    pub fn max_connections(mut self, max: u32) -> Self {
+        self.config.max_connections = Some(max);
+        self
+    }
+
+    pub fn min_connections(mut self, min: u32) -> Self {
+        self.config.min_connections = Some(min);
+        self
+    }
+
+    pub fn acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.config.acquire_timeout = Some(timeout);
+        self
+    }
+
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.config.idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn lazy(mut self, lazy: bool) -> Self {
+        self.config.lazy = lazy;
+        self
+    }
+
+    pub fn wal(mut self, wal: bool) -> Self {
+        self.config.wal = wal;
+        self
+    }
+
+    pub fn foreign_keys(mut self, on: bool) -> Self {
+        self.config.foreign_keys = Some(on);
+        self
+    }
+
+    pub fn connect_retries(mut self, retries: u32) -> Self {
+        self.config.connect_retries = Some(retries);
+        self
+    }
+
+    pub fn retry_backoff(mut self, backoff: Duration) -> Self {
+        self.config.retry_backoff = Some(backoff);
+        self
+    }
+
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    pub async fn build(self) -> Result<S, CapServiceError> {
+        S::from_pool_config(self.config).await
+    }
+}
+
+impl<S: FromPoolConfig> Default for PoolBuilder<S> {
+    fn default() -> Self {
+        PoolBuilder::new()
+    }
+}
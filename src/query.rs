@@ -0,0 +1,569 @@
+use std::cmp::Ordering;
+
+use actix_web::dev::Payload;
+use actix_web::{web, Error, FromRequest, HttpRequest};
+use async_trait::async_trait;
+use futures_util::future::{ready, Ready};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use sqlx::pool::Pool;
+use sqlx::{Database, FromRow, MySql, Postgres, Sqlite};
+
+use tokio::sync::mpsc;
+
+use crate::stream::receiver_stream;
+use crate::{CapServiceError, CapStream, Dialect, TableSchema, STREAM_BUFFER};
+
+const LIMIT: &str = "limit";
+const OFFSET: &str = "offset";
+const CURSOR: &str = "cursor";
+const SORT: &str = "sort";
+
+/// A value a filter compares a column with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Text(String),
+}
+
+macro_rules! filter_value {
+    ($variant:ident, $into:ty, $($type:ty),+) => {
+        $(
+            impl From<$type> for FilterValue {
+                fn from(value: $type) -> Self {
+                    FilterValue::$variant(<$into>::from(value))
+                }
+            }
+        )+
+    };
+}
+
+filter_value!(Int, i64, i8, i16, i32, i64, u8, u16, u32);
+filter_value!(Float, f64, f32, f64);
+filter_value!(Bool, bool, bool);
+filter_value!(Text, String, String);
+
+/// The fields a `Query` sorts by, generated by `#[capabilities(ReadAll, query)]` as `XField`.
+pub trait QueryField: Copy + Sized {
+    type Item;
+
+    fn column(&self) -> &'static str;
+
+    fn from_column(column: &str) -> Option<Self>;
+
+    fn compare(&self, a: &Self::Item, b: &Self::Item) -> Ordering;
+}
+
+/// The filters of a `Query`, generated next to `QueryField` as `XFilter` with an optional
+/// value per field. Set values have to be equal to the ones of the items.
+pub trait QueryFilter: Default + Sized {
+    type Item;
+
+    /// The filtered columns and their values, in the order of the fields.
+    fn conditions(&self) -> Vec<(&'static str, FilterValue)>;
+
+    fn matches(&self, item: &Self::Item) -> bool;
+
+    /// The filters as request parameters, named after their columns.
+    fn params(&self) -> Vec<(&'static str, String)>;
+
+    /// Sets the filter of `column` from a request parameter, false if there is none.
+    fn set_param(&mut self, column: &str, value: &str) -> Result<bool, String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort<S> {
+    pub field: S,
+    pub order: SortOrder,
+}
+
+/// The input of a `ReadAll` with `query`: which items, in which order and which page of them.
+///
+/// As request parameters it is `?name=Ada&sort=name,-id&limit=20&cursor=40`, a `-` sorts in
+/// descending order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query<F, S> {
+    pub filter: F,
+    pub sort: Vec<Sort<S>>,
+    pub limit: Option<u64>,
+    pub offset: u64,
+    /// The `next` of the previous page, it takes precedence over `offset`.
+    pub cursor: Option<String>,
+}
+
+impl<F: Default, S> Default for Query<F, S> {
+    fn default() -> Self {
+        Query {
+            filter: F::default(),
+            sort: vec![],
+            limit: None,
+            offset: 0,
+            cursor: None,
+        }
+    }
+}
+
+/// One page of the items of a `ReadAll` with `query`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// The number of items matching the filters, on all pages.
+    pub total: u64,
+    /// The cursor of the next page, none on the last one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, total: u64, start: u64) -> Self {
+        let end = start + items.len() as u64;
+        Page {
+            next: (end < total && !items.is_empty()).then(|| end.to_string()),
+            items,
+            total,
+        }
+    }
+}
+
+impl<F, S> Query<F, S>
+where
+    F: QueryFilter,
+    S: QueryField<Item = F::Item>,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn filter(mut self, filter: F) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn sort_by(mut self, field: S, order: SortOrder) -> Self {
+        self.sort.push(Sort { field, order });
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn after(mut self, cursor: impl Into<String>) -> Self {
+        self.cursor = Some(cursor.into());
+        self
+    }
+
+    /// The index of the first item of the page.
+    pub fn start(&self) -> Result<u64, CapServiceError> {
+        match &self.cursor {
+            Some(cursor) => cursor
+                .parse()
+                .map_err(|_| CapServiceError::Config(format!("Invalid cursor {}", cursor))),
+            None => Ok(self.offset),
+        }
+    }
+
+    /// The values of the placeholders in `select_sql` and `count_sql`.
+    pub fn values(&self) -> Vec<FilterValue> {
+        self.filter
+            .conditions()
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    fn where_sql(&self, dialect: Dialect) -> String {
+        let conditions: Vec<String> = self
+            .filter
+            .conditions()
+            .iter()
+            .enumerate()
+            .map(|(i, (column, _))| match dialect {
+                Dialect::Postgres => format!("{} = ${}", column, i + 1),
+                Dialect::Sqlite | Dialect::MySql => format!("{} = ?", column),
+            })
+            .collect();
+        if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        }
+    }
+
+    pub fn select_sql(&self, table: &str, dialect: Dialect) -> Result<String, CapServiceError> {
+        let mut sql = format!("SELECT * FROM {}{}", table, self.where_sql(dialect));
+        if !self.sort.is_empty() {
+            let order: Vec<String> = self
+                .sort
+                .iter()
+                .map(|s| match s.order {
+                    SortOrder::Asc => format!("{} ASC", s.field.column()),
+                    SortOrder::Desc => format!("{} DESC", s.field.column()),
+                })
+                .collect();
+            sql.push_str(&format!(" ORDER BY {}", order.join(", ")));
+        }
+        let start = self.start()?;
+        match (self.limit, dialect) {
+            (Some(limit), _) => sql.push_str(&format!(" LIMIT {}", limit)),
+            // Sqlite and MySQL only take an offset after a limit.
+            (None, Dialect::Sqlite) if start > 0 => sql.push_str(" LIMIT -1"),
+            (None, Dialect::MySql) if start > 0 => sql.push_str(&format!(" LIMIT {}", u64::MAX)),
+            (None, _) => {}
+        }
+        if start > 0 {
+            sql.push_str(&format!(" OFFSET {}", start));
+        }
+        Ok(sql)
+    }
+
+    pub fn count_sql(&self, table: &str, dialect: Dialect) -> String {
+        format!("SELECT COUNT(*) FROM {}{}", table, self.where_sql(dialect))
+    }
+
+    /// Filters, sorts and pages items that were read as a whole, e.g. from a `FileStore`.
+    pub fn apply(&self, mut items: Vec<F::Item>) -> Result<Page<F::Item>, CapServiceError> {
+        let start = self.start()?;
+        items.retain(|item| self.filter.matches(item));
+        items.sort_by(|a, b| {
+            self.sort
+                .iter()
+                .map(|s| match s.order {
+                    SortOrder::Asc => s.field.compare(a, b),
+                    SortOrder::Desc => s.field.compare(b, a),
+                })
+                .find(|o| o.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        let total = items.len() as u64;
+        let items = items
+            .into_iter()
+            .skip(start as usize)
+            .take(self.limit.map_or(usize::MAX, |l| l as usize))
+            .collect();
+        Ok(Page::new(items, total, start))
+    }
+
+    /// Reads the page and the total from the table of the items.
+    pub async fn fetch_page<DB>(&self, pool: &Pool<DB>) -> Result<Page<F::Item>, CapServiceError>
+    where
+        DB: QueryDatabase,
+        F::Item: TableSchema + for<'r> FromRow<'r, DB::Row> + Send + Unpin,
+    {
+        let table = <F::Item as TableSchema>::TABLE;
+        let select = self.select_sql(table, DB::DIALECT)?;
+        let count = self.count_sql(table, DB::DIALECT);
+        let (items, total) = DB::fetch(pool, &select, &count, &self.values()).await?;
+        Ok(Page::new(items, total, self.start()?))
+    }
+
+    /// Streams the items, filtered and sorted, from the table of the items. Limit and offset
+    /// apply as they do to `fetch_page`.
+    pub fn stream<DB>(&self, pool: &Pool<DB>) -> Result<CapStream<F::Item>, CapServiceError>
+    where
+        DB: QueryDatabase,
+        F::Item: TableSchema + for<'r> FromRow<'r, DB::Row> + Send + Unpin + 'static,
+    {
+        let select = self.select_sql(<F::Item as TableSchema>::TABLE, DB::DIALECT)?;
+        Ok(DB::stream(pool.clone(), select, self.values()))
+    }
+
+    /// The query as request parameters, see `from_params`.
+    pub fn params(&self) -> Vec<(String, String)> {
+        let mut params: Vec<(String, String)> = self
+            .filter
+            .params()
+            .into_iter()
+            .map(|(column, value)| (column.to_string(), value))
+            .collect();
+        if !self.sort.is_empty() {
+            let sort: Vec<String> = self
+                .sort
+                .iter()
+                .map(|s| match s.order {
+                    SortOrder::Asc => s.field.column().to_string(),
+                    SortOrder::Desc => format!("-{}", s.field.column()),
+                })
+                .collect();
+            params.push((SORT.to_string(), sort.join(",")));
+        }
+        if let Some(limit) = self.limit {
+            params.push((LIMIT.to_string(), limit.to_string()));
+        }
+        if self.offset > 0 {
+            params.push((OFFSET.to_string(), self.offset.to_string()));
+        }
+        if let Some(cursor) = &self.cursor {
+            params.push((CURSOR.to_string(), cursor.clone()));
+        }
+        params
+    }
+
+    pub fn from_params(params: Vec<(String, String)>) -> Result<Self, String> {
+        let mut query = Self::default();
+        for (name, value) in params {
+            match name.as_str() {
+                LIMIT => query.limit = Some(parse_number(&name, &value)?),
+                OFFSET => query.offset = parse_number(&name, &value)?,
+                CURSOR => {
+                    parse_number(&name, &value)?;
+                    query.cursor = Some(value);
+                }
+                SORT => {
+                    for column in value.split(',').filter(|c| !c.is_empty()) {
+                        let (column, order) = match column.strip_prefix('-') {
+                            Some(column) => (column, SortOrder::Desc),
+                            None => (column, SortOrder::Asc),
+                        };
+                        let field = S::from_column(column)
+                            .ok_or_else(|| format!("Cannot sort by {}", column))?;
+                        query.sort.push(Sort { field, order });
+                    }
+                }
+                column => {
+                    if !query.filter.set_param(column, &value)? {
+                        return Err(format!("Unknown parameter {}", column));
+                    }
+                }
+            }
+        }
+        Ok(query)
+    }
+}
+
+fn parse_number(name: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} takes a number, not {}", name, value))
+}
+
+impl<F, S> FromRequest for Query<F, S>
+where
+    F: QueryFilter,
+    S: QueryField<Item = F::Item>,
+{
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let query = web::Query::<Vec<(String, String)>>::from_query(req.query_string())
+            .map_err(Error::from)
+            .and_then(|params| {
+                Query::from_params(params.into_inner()).map_err(actix_web::error::ErrorBadRequest)
+            });
+        ready(query)
+    }
+}
+
+/// The databases `Query::fetch_page` and `fetch_stream` read from.
+#[async_trait]
+pub trait QueryDatabase: Database {
+    const DIALECT: Dialect;
+
+    async fn fetch<T>(
+        pool: &Pool<Self>,
+        select: &str,
+        count: &str,
+        values: &[FilterValue],
+    ) -> Result<(Vec<T>, u64), CapServiceError>
+    where
+        T: for<'r> FromRow<'r, Self::Row> + Send + Unpin;
+
+    /// Sends the rows of `select` into the stream as sqlx fetches them, from a task of its own.
+    fn stream<T>(pool: Pool<Self>, select: String, values: Vec<FilterValue>) -> CapStream<T>
+    where
+        T: for<'r> FromRow<'r, Self::Row> + Send + Unpin + 'static;
+}
+
+macro_rules! query_database {
+    ($db:ty, $dialect:expr) => {
+        #[async_trait]
+        impl QueryDatabase for $db {
+            const DIALECT: Dialect = $dialect;
+
+            async fn fetch<T>(
+                pool: &Pool<Self>,
+                select: &str,
+                count: &str,
+                values: &[FilterValue],
+            ) -> Result<(Vec<T>, u64), CapServiceError>
+            where
+                T: for<'r> FromRow<'r, Self::Row> + Send + Unpin,
+            {
+                let mut items = sqlx::query_as::<_, T>(select);
+                let mut total = sqlx::query_as::<_, (i64,)>(count);
+                for value in values {
+                    (items, total) = match value.clone() {
+                        FilterValue::Int(v) => (items.bind(v), total.bind(v)),
+                        FilterValue::Float(v) => (items.bind(v), total.bind(v)),
+                        FilterValue::Bool(v) => (items.bind(v), total.bind(v)),
+                        FilterValue::Text(v) => (items.bind(v.clone()), total.bind(v)),
+                    };
+                }
+                let items = items.fetch_all(pool).await?;
+                let (total,) = total.fetch_one(pool).await?;
+                Ok((items, total as u64))
+            }
+
+            fn stream<T>(pool: Pool<Self>, select: String, values: Vec<FilterValue>) -> CapStream<T>
+            where
+                T: for<'r> FromRow<'r, Self::Row> + Send + Unpin + 'static,
+            {
+                let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+                tokio::spawn(async move {
+                    let mut items = sqlx::query_as::<_, T>(&select);
+                    for value in values {
+                        items = match value {
+                            FilterValue::Int(v) => items.bind(v),
+                            FilterValue::Float(v) => items.bind(v),
+                            FilterValue::Bool(v) => items.bind(v),
+                            FilterValue::Text(v) => items.bind(v),
+                        };
+                    }
+                    let mut rows = items.fetch(&pool);
+                    while let Some(row) = rows.next().await {
+                        let failed = row.is_err();
+                        // The receiver is gone when the stream was dropped.
+                        if sender
+                            .send(row.map_err(CapServiceError::from))
+                            .await
+                            .is_err()
+                            || failed
+                        {
+                            break;
+                        }
+                    }
+                });
+                receiver_stream(receiver)
+            }
+        }
+    };
+}
+
+query_database!(Sqlite, Dialect::Sqlite);
+query_database!(Postgres, Dialect::Postgres);
+query_database!(MySql, Dialect::MySql);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Note {
+        id: i32,
+        title: String,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum NoteField {
+        Id,
+        Title,
+    }
+
+    impl QueryField for NoteField {
+        type Item = Note;
+
+        fn column(&self) -> &'static str {
+            match self {
+                NoteField::Id => "id",
+                NoteField::Title => "title",
+            }
+        }
+
+        fn from_column(column: &str) -> Option<Self> {
+            match column {
+                "id" => Some(NoteField::Id),
+                "title" => Some(NoteField::Title),
+                _ => None,
+            }
+        }
+
+        fn compare(&self, a: &Note, b: &Note) -> Ordering {
+            match self {
+                NoteField::Id => a.id.cmp(&b.id),
+                NoteField::Title => a.title.cmp(&b.title),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq)]
+    struct NoteFilter {
+        title: Option<String>,
+    }
+
+    impl QueryFilter for NoteFilter {
+        type Item = Note;
+
+        fn conditions(&self) -> Vec<(&'static str, FilterValue)> {
+            self.title
+                .iter()
+                .map(|t| ("title", FilterValue::from(t.clone())))
+                .collect()
+        }
+
+        fn matches(&self, item: &Note) -> bool {
+            self.title.iter().all(|t| t == &item.title)
+        }
+
+        fn params(&self) -> Vec<(&'static str, String)> {
+            self.title.iter().map(|t| ("title", t.clone())).collect()
+        }
+
+        fn set_param(&mut self, column: &str, value: &str) -> Result<bool, String> {
+            match column {
+                "title" => self.title = Some(value.to_string()),
+                _ => return Ok(false),
+            }
+            Ok(true)
+        }
+    }
+
+    type NoteQuery = Query<NoteFilter, NoteField>;
+
+    #[test]
+    fn page_through_filtered_items() {
+        let notes: Vec<Note> = (1..=5)
+            .map(|id| Note {
+                id,
+                title: if id % 2 == 0 { "even" } else { "odd" }.to_string(),
+            })
+            .collect();
+        let query = NoteQuery::new()
+            .filter(NoteFilter {
+                title: Some("odd".to_string()),
+            })
+            .sort_by(NoteField::Id, SortOrder::Desc)
+            .limit(2);
+
+        let page = query.apply(notes.clone()).unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.items.iter().map(|n| n.id).collect::<Vec<_>>(), [5, 3]);
+        assert_eq!(page.next.as_deref(), Some("2"));
+
+        let page = query.clone().after("2").apply(notes).unwrap();
+        assert_eq!(page.items.iter().map(|n| n.id).collect::<Vec<_>>(), [1]);
+        assert_eq!(page.next, None);
+
+        assert_eq!(
+            query.select_sql("notes", Dialect::Postgres).unwrap(),
+            "SELECT * FROM notes WHERE title = $1 ORDER BY id DESC LIMIT 2"
+        );
+        let params = query.params();
+        assert_eq!(NoteQuery::from_params(params).unwrap(), query);
+        assert!(NoteQuery::from_params(vec![("body".into(), "x".into())]).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use std::borrow::Cow;
+
+use crate::Capability;
+
+/// The pool a database `CapService` performs an operation on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    Primary,
+    Replica,
+}
+
+impl Route {
+    /// `Read` and `ReadAll` go to the replica unless the capability function pins a route.
+    pub fn select(required: Capability, pinned: Option<Route>) -> Route {
+        match (pinned, required) {
+            (Some(route), _) => route,
+            (None, Capability::Read) | (None, Capability::ReadAll) => Route::Replica,
+            (None, _) => Route::Primary,
+        }
+    }
+}
+
+/// Picks the service a granted operation is performed on.
+///
+/// Every generated `CapService` implements it, only the database services with a replica
+/// hand out another service.
+pub trait Routing: Clone {
+    fn route(&self, _required: Capability, _pinned: Option<Route>) -> Cow<'_, Self> {
+        Cow::Borrowed(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn route_reads_to_the_replica() {
+        assert_eq!(Route::select(Capability::ReadAll, None), Route::Replica);
+        assert_eq!(Route::select(Capability::Update, None), Route::Primary);
+        assert_eq!(
+            Route::select(Capability::Read, Some(Route::Primary)),
+            Route::Primary
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+    MySql,
+}
+
+/// Implemented by `#[capabilities(..., table = "name")]` structs.
+pub trait TableSchema {
+    const TABLE: &'static str;
+
+    fn create_table(dialect: Dialect) -> &'static str;
+
+    fn schema() -> Schema {
+        Schema {
+            table: Self::TABLE,
+            create_table: Self::create_table,
+        }
+    }
+}
+
+/// The schema of one `TableSchema` struct, so that several can be dumped together.
+#[derive(Clone, Copy)]
+pub struct Schema {
+    pub table: &'static str,
+    pub create_table: fn(Dialect) -> &'static str,
+}
+
+/// Writes the schemas into `<dir>/<version>_create_tables.sql`, a migration `sqlx::migrate!` picks up.
+pub fn dump_schemas(
+    dir: impl AsRef<Path>,
+    version: i64,
+    dialect: Dialect,
+    schemas: &[Schema],
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir.as_ref())?;
+    let path = dir.as_ref().join(format!("{}_create_tables.sql", version));
+
+    let mut sql = String::new();
+    for schema in schemas {
+        sql.push_str(&format!("-- {}\n", schema.table));
+        sql.push_str((schema.create_table)(dialect));
+        sql.push_str("\n\n");
+    }
+    fs::write(&path, sql)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Orders;
+
+    impl TableSchema for Orders {
+        const TABLE: &'static str = "orders";
+
+        fn create_table(_dialect: Dialect) -> &'static str {
+            "CREATE TABLE IF NOT EXISTS orders (\n    id INTEGER NOT NULL,\n    PRIMARY KEY (id)\n);"
+        }
+    }
+
+    #[test]
+    fn dump_schemas_as_migration() {
+        let dir = std::env::temp_dir().join("capabilities_dump_schemas");
+        let path = dump_schemas(&dir, 1, Dialect::Sqlite, &[Orders::schema()])
+            .expect("Failed to write migration");
+
+        assert!(path.ends_with("1_create_tables.sql"));
+        let sql = fs::read_to_string(&path).expect("Failed to read migration");
+        assert!(sql.contains("-- orders"));
+        assert!(sql.contains(Orders::create_table(Dialect::Sqlite)));
+
+        fs::remove_dir_all(dir).expect("Failed to clean up");
+    }
+}
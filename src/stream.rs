@@ -0,0 +1,189 @@
+use std::pin::Pin;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use actix_web::body::BoxBody;
+use actix_web::dev::Payload;
+use actix_web::web::Bytes;
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest, HttpResponse, Responder};
+use futures_util::future::{ready, Ready};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use sqlx::pool::Pool;
+use sqlx::FromRow;
+use tokio::sync::mpsc;
+
+use crate::{CapServiceError, Capability, Grant, Projection, QueryDatabase};
+
+/// Rows a `fetch_stream` reads ahead of its consumer.
+pub const STREAM_BUFFER: usize = 64;
+
+/// The items of a `StreamAll`, one at a time as the backend delivers them.
+pub type CapStream<T> = Pin<Box<dyn Stream<Item = Result<T, CapServiceError>> + Send>>;
+
+/// A grant presented for a limited time, like the lifetime of the token granting it.
+///
+/// A `Capability` or a `Grant` converts into a lease that never expires. The fields of the grant
+/// are what the items of a stream are projected with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    pub grant: Grant,
+    pub expires_at: Option<Instant>,
+}
+
+impl Lease {
+    pub fn new(grant: impl Into<Grant>) -> Self {
+        Lease {
+            grant: grant.into(),
+            expires_at: None,
+        }
+    }
+
+    pub fn capability(&self) -> Capability {
+        self.grant.capability
+    }
+
+    pub fn expires_in(mut self, duration: Duration) -> Self {
+        self.expires_at = Some(Instant::now() + duration);
+        self
+    }
+
+    /// Expires `secs` seconds after the Unix epoch, like the `exp` of a token.
+    pub fn expires_at_unix(mut self, secs: u64) -> Self {
+        let expiry = UNIX_EPOCH + Duration::from_secs(secs);
+        let remaining = expiry.duration_since(SystemTime::now()).unwrap_or_default();
+        self.expires_at = Some(Instant::now() + remaining);
+        self
+    }
+
+    pub fn is_expired(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => Instant::now() >= expires_at,
+            None => false,
+        }
+    }
+
+    /// Ends the stream with `CapServiceError::Expired` once the lease expires, the items
+    /// that are left are not read.
+    pub fn guard<T: Send + 'static>(self, stream: CapStream<T>) -> CapStream<T> {
+        let mut expired = false;
+        let stream = stream
+            .map(move |item| match self.is_expired() {
+                true => Err(CapServiceError::Expired),
+                false => item,
+            })
+            .take_while(move |item| {
+                let open = !expired;
+                expired = matches!(item, Err(CapServiceError::Expired));
+                ready(open)
+            });
+        Box::pin(stream)
+    }
+}
+
+impl From<Capability> for Lease {
+    fn from(capability: Capability) -> Self {
+        Lease::new(capability)
+    }
+}
+
+impl From<Grant> for Lease {
+    fn from(grant: Grant) -> Self {
+        Lease::new(grant)
+    }
+}
+
+/// The lease `token_introspection` put in the request, expiring with the token, or else its
+/// `Grant` or `Capability` without expiry.
+impl FromRequest for Lease {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let extensions = req.extensions();
+        let lease = match extensions.get::<Lease>() {
+            Some(lease) => Ok(lease.clone()),
+            None => extensions
+                .get::<Grant>()
+                .map(|g| Lease::new(g.clone()))
+                .or_else(|| extensions.get::<Capability>().map(|c| Lease::new(*c)))
+                .ok_or_else(|| actix_web::error::ErrorForbidden("No capability presented")),
+        };
+        ready(lease)
+    }
+}
+
+/// Projects the items as they arrive.
+impl<T> Projection for CapStream<T>
+where
+    T: Projection + Send + 'static,
+    T::View: Send + 'static,
+{
+    type View = CapStream<T::View>;
+
+    fn project(self, grant: &Grant) -> Self::View {
+        let grant = grant.clone();
+        Box::pin(self.map(move |item| item.map(|item| item.project(&grant))))
+    }
+}
+
+/// Streams the rows of `sql` as sqlx fetches them. A task of its own reads them, at most
+/// `STREAM_BUFFER` rows ahead, and stops when the stream is dropped.
+pub fn fetch_stream<DB, T>(pool: &Pool<DB>, sql: impl Into<String>) -> CapStream<T>
+where
+    DB: QueryDatabase,
+    T: for<'r> FromRow<'r, DB::Row> + Send + Unpin + 'static,
+{
+    DB::stream(pool.clone(), sql.into(), vec![])
+}
+
+/// Turns the receiving end of a `fetch_stream` task into its stream.
+pub(crate) fn receiver_stream<T: Send + 'static>(
+    receiver: mpsc::Receiver<Result<T, CapServiceError>>,
+) -> CapStream<T> {
+    let stream = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|item| (item, receiver))
+    });
+    Box::pin(stream)
+}
+
+/// Responds with a `CapStream` as newline delimited JSON, one item per line.
+///
+/// The items are written as they arrive, so an error after the first item cuts the response
+/// short instead of changing its status.
+pub struct NdJson<T>(pub CapStream<T>);
+
+impl<T: Serialize + 'static> Responder for NdJson<T> {
+    type Body = BoxBody;
+
+    fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let lines = self.0.map(|item| {
+            let mut line = serde_json::to_vec(&item?).map_err(CapServiceError::from_backend)?;
+            line.push(b'\n');
+            Ok::<_, CapServiceError>(Bytes::from(line))
+        });
+        HttpResponse::Ok()
+            .content_type("application/x-ndjson")
+            .streaming(lines)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn expired_lease_ends_stream() {
+        let items: CapStream<u32> = Box::pin(stream::iter(vec![Ok(1), Ok(2)]));
+        let lease = Lease::new(Capability::ReadAll).expires_in(Duration::ZERO);
+        let items: Vec<_> = lease.guard(items).collect().await;
+        assert_eq!(items.len(), 1);
+        assert!(matches!(items[0], Err(CapServiceError::Expired)));
+
+        let items: CapStream<u32> = Box::pin(stream::iter(vec![Ok(1), Ok(2)]));
+        let items: Vec<_> = Lease::from(Capability::ReadAll)
+            .guard(items)
+            .collect()
+            .await;
+        assert_eq!(items.len(), 2);
+    }
+}
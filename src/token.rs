@@ -0,0 +1,221 @@
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix_web::dev::Payload;
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest};
+use async_trait::async_trait;
+use futures_util::future::{ready, Ready};
+use futures_util::lock::Mutex;
+use gnap_cli::GnapClient;
+
+use crate::CapServiceError;
+
+/// Tokens are refreshed this long before they expire, so they do not expire in flight.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct AccessToken {
+    pub value: String,
+    pub expires_at: Option<Instant>,
+}
+
+impl AccessToken {
+    pub fn new(value: impl Into<String>) -> Self {
+        AccessToken {
+            value: value.into(),
+            expires_at: None,
+        }
+    }
+
+    pub fn expires_in(mut self, duration: Duration) -> Self {
+        self.expires_at = Some(Instant::now() + duration);
+        self
+    }
+
+    pub fn is_expired(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => Instant::now() + EXPIRY_MARGIN >= expires_at,
+            None => false,
+        }
+    }
+}
+
+impl fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessToken")
+            .field("value", &"***")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// Hands out access tokens for outgoing `WebService` requests.
+#[async_trait]
+pub trait TokenProvider: Send + Sync {
+    async fn fetch(&self) -> Result<AccessToken, CapServiceError>;
+}
+
+#[async_trait]
+impl<F, Fut> TokenProvider for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<AccessToken, CapServiceError>> + Send,
+{
+    async fn fetch(&self) -> Result<AccessToken, CapServiceError> {
+        self().await
+    }
+}
+
+/// A token from a GNAP grant, `request` asks for the grant through the `GnapClient`.
+pub struct GnapGrant<F> {
+    client: Arc<GnapClient>,
+    request: F,
+}
+
+impl<F> GnapGrant<F> {
+    pub fn new(client: GnapClient, request: F) -> Self {
+        GnapGrant {
+            client: Arc::new(client),
+            request,
+        }
+    }
+}
+
+#[async_trait]
+impl<F, Fut> TokenProvider for GnapGrant<F>
+where
+    F: Fn(Arc<GnapClient>) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<AccessToken, CapServiceError>> + Send,
+    GnapClient: Send + Sync,
+{
+    async fn fetch(&self) -> Result<AccessToken, CapServiceError> {
+        (self.request)(self.client.clone()).await
+    }
+}
+
+/// Keeps the token of a `TokenProvider` until it expires.
+pub struct CachedToken {
+    provider: Box<dyn TokenProvider>,
+    token: Mutex<Option<AccessToken>>,
+}
+
+impl CachedToken {
+    pub async fn token(&self) -> Result<AccessToken, CapServiceError> {
+        let mut token = self.token.lock().await;
+        match token.as_ref() {
+            Some(t) if !t.is_expired() => Ok(t.clone()),
+            _ => {
+                let fresh = self.provider.fetch().await?;
+                *token = Some(fresh.clone());
+                Ok(fresh)
+            }
+        }
+    }
+
+    /// Drops the cached token, e.g. after the API rejected it.
+    pub async fn invalidate(&self) {
+        *self.token.lock().await = None;
+    }
+}
+
+/// Where a `WebService` gets the bearer token for outgoing requests.
+#[derive(Clone, Default)]
+pub enum TokenSource {
+    #[default]
+    None,
+    Static(String),
+    /// The token of the incoming request, see `BearerToken`.
+    Forwarded(String),
+    Provider(Arc<CachedToken>),
+}
+
+impl TokenSource {
+    pub fn provider(provider: impl TokenProvider + 'static) -> Self {
+        TokenSource::Provider(Arc::new(CachedToken {
+            provider: Box::new(provider),
+            token: Mutex::new(None),
+        }))
+    }
+
+    pub async fn token(&self) -> Result<Option<String>, CapServiceError> {
+        match self {
+            TokenSource::None => Ok(None),
+            TokenSource::Static(token) | TokenSource::Forwarded(token) => Ok(Some(token.clone())),
+            TokenSource::Provider(cached) => Ok(Some(cached.token().await?.value)),
+        }
+    }
+}
+
+impl fmt::Debug for TokenSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenSource::None => write!(f, "None"),
+            TokenSource::Static(_) => write!(f, "Static(***)"),
+            TokenSource::Forwarded(_) => write!(f, "Forwarded(***)"),
+            TokenSource::Provider(_) => write!(f, "Provider"),
+        }
+    }
+}
+
+/// The bearer token of the incoming request, put there by `token_introspection`.
+#[derive(Clone)]
+pub struct BearerToken(pub String);
+
+impl BearerToken {
+    /// Sends the incoming token on to the downstream API as it is.
+    pub fn forward(&self) -> TokenSource {
+        TokenSource::Forwarded(self.0.clone())
+    }
+
+    /// Trades the incoming token for one of the downstream API, e.g. through a GNAP grant.
+    pub fn exchange<F, Fut>(&self, exchange: F) -> TokenSource
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<AccessToken, CapServiceError>> + Send + 'static,
+    {
+        let token = self.0.clone();
+        TokenSource::provider(move || exchange(token.clone()))
+    }
+}
+
+impl fmt::Debug for BearerToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BearerToken(***)")
+    }
+}
+
+impl FromRequest for BearerToken {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let token = req.extensions().get::<BearerToken>().cloned();
+        ready(token.ok_or_else(|| actix_web::error::ErrorUnauthorized("Missing bearer token")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn refresh_expired_tokens() {
+        let fetched = Arc::new(AtomicUsize::new(0));
+        let counter = fetched.clone();
+        let source = TokenSource::provider(move || {
+            let counter = counter.clone();
+            async move {
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                // The first token is already inside the expiry margin.
+                Ok(AccessToken::new(format!("token-{}", n)).expires_in(Duration::from_secs(1)))
+            }
+        });
+
+        assert_eq!(source.token().await.unwrap(), Some("token-0".to_string()));
+        assert_eq!(source.token().await.unwrap(), Some("token-1".to_string()));
+        assert_eq!(fetched.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,150 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use sqlx::pool::{Pool, PoolConnection};
+use sqlx::{Database, Transaction};
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::CapServiceError;
+
+/// Names the database behind a pool type like `SqliteDb`.
+pub trait PoolDatabase {
+    type Database: Database;
+}
+
+impl<DB: Database> PoolDatabase for Pool<DB> {
+    type Database = DB;
+}
+
+type Shared<DB> = Arc<Mutex<Option<Transaction<'static, DB>>>>;
+
+/// The transaction a database `CapService` runs its capability functions in, if any.
+///
+/// Clones share the transaction, it is committed or rolled back once by `finish`. Only the
+/// connections of `connection` take part in it. Queries on the pool itself run outside of the
+/// transaction, and with a pool of one connection they wait for it forever while a
+/// `DbConnection::Transaction` is held.
+pub struct TxSlot<DB: Database> {
+    tx: Option<Shared<DB>>,
+}
+
+impl<DB: Database> TxSlot<DB> {
+    pub async fn begin(pool: &Pool<DB>) -> Result<Self, CapServiceError> {
+        let tx = pool.begin().await?;
+        Ok(TxSlot {
+            tx: Some(Arc::new(Mutex::new(Some(tx)))),
+        })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.tx.is_some()
+    }
+
+    /// The connection of the transaction, or one of the pool outside of a transaction.
+    pub async fn connection(
+        &self,
+        pool: &Pool<DB>,
+    ) -> Result<DbConnection<'_, DB>, CapServiceError> {
+        match &self.tx {
+            Some(tx) => {
+                let guard = tx.lock().await;
+                if guard.is_none() {
+                    return Err(CapServiceError::Config(
+                        "The transaction is already finished".to_string(),
+                    ));
+                }
+                Ok(DbConnection::Transaction(guard))
+            }
+            None => Ok(DbConnection::Pool(pool.acquire().await?)),
+        }
+    }
+
+    /// Commits the transaction, or rolls it back when `commit` is false.
+    pub async fn finish(&self, commit: bool) -> Result<(), CapServiceError> {
+        let tx = match &self.tx {
+            Some(tx) => tx.lock().await.take(),
+            None => None,
+        };
+        match tx {
+            Some(tx) if commit => Ok(tx.commit().await?),
+            Some(tx) => Ok(tx.rollback().await?),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<DB: Database> Clone for TxSlot<DB> {
+    fn clone(&self) -> Self {
+        TxSlot {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<DB: Database> Default for TxSlot<DB> {
+    fn default() -> Self {
+        TxSlot { tx: None }
+    }
+}
+
+/// A connection handed out by `CapService::connection`, use it as `&mut *conn` with sqlx.
+pub enum DbConnection<'a, DB: Database> {
+    Pool(PoolConnection<DB>),
+    Transaction(MutexGuard<'a, Option<Transaction<'static, DB>>>),
+}
+
+impl<DB: Database> Deref for DbConnection<'_, DB> {
+    type Target = DB::Connection;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            DbConnection::Pool(con) => con,
+            // `TxSlot::connection` only hands out guards holding a transaction.
+            DbConnection::Transaction(tx) => tx.as_ref().expect("transaction is finished"),
+        }
+    }
+}
+
+impl<DB: Database> DerefMut for DbConnection<'_, DB> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            DbConnection::Pool(con) => con,
+            DbConnection::Transaction(tx) => tx.as_mut().expect("transaction is finished"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sqlx::sqlite::{Sqlite, SqlitePoolOptions};
+
+    #[tokio::test]
+    async fn roll_back_unless_committed() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE notes (id INTEGER PRIMARY KEY)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let slot = TxSlot::<Sqlite>::begin(&pool).await.unwrap();
+        let mut con = slot.connection(&pool).await.unwrap();
+        sqlx::query("INSERT INTO notes (id) VALUES (1)")
+            .execute(&mut *con)
+            .await
+            .unwrap();
+        drop(con);
+        slot.finish(false).await.unwrap();
+        assert!(slot.connection(&pool).await.is_err());
+
+        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM notes")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count.0, 0);
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+use std::sync::RwLock;
+
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// The verbs `register_verb` made known to `token_introspection`.
+static VERBS: RwLock<Vec<&'static str>> = RwLock::new(Vec::new());
+
+/// A verb beyond CRUD, like `Approve` in `#[capabilities(Read, Approve(input = Note))]`.
+///
+/// It is named like the actions of a token, in lowercase, and compares by its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Verb(&'static str);
+
+impl Verb {
+    pub const fn new(name: &'static str) -> Self {
+        Verb(name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+
+    /// The registered verb called `name`.
+    pub fn lookup(name: &str) -> Option<Verb> {
+        let verbs = VERBS.read().unwrap_or_else(|e| e.into_inner());
+        verbs.iter().find(|v| **v == name).map(|v| Verb(v))
+    }
+}
+
+impl fmt::Display for Verb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Lets tokens grant the verb: an action `"approve"` becomes `Capability::Custom(verb)`
+/// instead of `Capability::Invalid`. Verbs also have to be registered wherever a
+/// `Capability` carrying them is deserialized, e.g. by the clients of the service.
+/// The generated `register_verbs` of a `#[capabilities]` struct registers all of its verbs.
+pub fn register_verb(name: &'static str) -> Verb {
+    let mut verbs = VERBS.write().unwrap_or_else(|e| e.into_inner());
+    if !verbs.contains(&name) {
+        verbs.push(name);
+    }
+    Verb(name)
+}
+
+impl Serialize for Verb {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Verb {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Verb::lookup(&name)
+            .ok_or_else(|| de::Error::custom(format!("unknown verb {}, see register_verb", name)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deserialize_registered_verbs() {
+        let refund = register_verb("refund");
+        assert_eq!(Verb::lookup("refund"), Some(Verb::new("refund")));
+
+        let json = serde_json::to_string(&refund).unwrap();
+        assert_eq!(json, "\"refund\"");
+        assert_eq!(serde_json::from_str::<Verb>(&json).unwrap(), refund);
+        assert!(serde_json::from_str::<Verb>("\"deserialize_unregistered\"").is_err());
+    }
+}
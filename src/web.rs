@@ -0,0 +1,292 @@
+use std::ops::Deref;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Certificate, Client, Method, RequestBuilder, Url};
+
+use crate::{CapServiceError, ServiceBackend, TokenSource};
+
+/// Settings for the reqwest client behind `WebService`.
+#[derive(Debug, Clone, Default)]
+pub struct WebServiceConfig {
+    pub base_url: Option<String>,
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub user_agent: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub accept_invalid_certs: bool,
+    /// PEM encoded root certificates to trust besides the system ones.
+    pub root_certificates: Vec<Vec<u8>>,
+    pub token: TokenSource,
+    /// The variable holding a static token, read by `build` unless `token` is set.
+    pub token_env: Option<String>,
+}
+
+impl WebServiceConfig {
+    pub fn new() -> Self {
+        WebServiceConfig::default()
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    pub fn root_certificate(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    pub fn token(mut self, token: TokenSource) -> Self {
+        self.token = token;
+        self
+    }
+
+    pub fn token_env(mut self, name: impl Into<String>) -> Self {
+        self.token_env = Some(name.into());
+        self
+    }
+}
+
+/// The `WebService` backend, a reqwest `Client` that knows the base URL of the API it talks to.
+///
+/// Derefs to the `Client`, so capability bodies can still use it as one. The request helpers
+/// `authed_get`, `authed_post`, ... start requests carrying the bearer of the `TokenSource`, the
+/// methods of the `Client` are left as they are.
+#[derive(Debug, Clone)]
+pub struct WebService {
+    client: Client,
+    base_url: Option<Url>,
+    token: TokenSource,
+}
+
+impl WebService {
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn base_url(&self) -> Option<&Url> {
+        self.base_url.as_ref()
+    }
+
+    /// Joins `path` onto the base URL, absolute URLs are used as they are.
+    pub fn url(&self, path: &str) -> Result<Url, CapServiceError> {
+        if let Ok(url) = Url::parse(path) {
+            return Ok(url);
+        }
+        let base_url = self
+            .base_url
+            .as_ref()
+            .ok_or_else(|| CapServiceError::Config(format!("No base_url to join {} onto", path)))?;
+        base_url
+            .join(path.trim_start_matches('/'))
+            .map_err(|e| CapServiceError::Config(e.to_string()))
+    }
+
+    pub fn token(&self) -> &TokenSource {
+        &self.token
+    }
+
+    /// The same service, but sending `token` with its requests.
+    pub fn with_token(&self, token: TokenSource) -> Self {
+        WebService {
+            token,
+            ..self.clone()
+        }
+    }
+
+    /// Starts a request to `path`, carrying a valid bearer token if the service has one.
+    pub async fn authed_request(
+        &self,
+        method: Method,
+        path: &str,
+    ) -> Result<RequestBuilder, CapServiceError> {
+        let request = self.client.request(method, self.url(path)?);
+        match self.token.token().await? {
+            Some(token) => Ok(request.bearer_auth(token)),
+            None => Ok(request),
+        }
+    }
+
+    pub async fn authed_get(&self, path: &str) -> Result<RequestBuilder, CapServiceError> {
+        self.authed_request(Method::GET, path).await
+    }
+
+    pub async fn authed_post(&self, path: &str) -> Result<RequestBuilder, CapServiceError> {
+        self.authed_request(Method::POST, path).await
+    }
+
+    pub async fn authed_put(&self, path: &str) -> Result<RequestBuilder, CapServiceError> {
+        self.authed_request(Method::PUT, path).await
+    }
+
+    pub async fn authed_patch(&self, path: &str) -> Result<RequestBuilder, CapServiceError> {
+        self.authed_request(Method::PATCH, path).await
+    }
+
+    pub async fn authed_delete(&self, path: &str) -> Result<RequestBuilder, CapServiceError> {
+        self.authed_request(Method::DELETE, path).await
+    }
+}
+
+impl Deref for WebService {
+    type Target = Client;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+fn parse_base_url(base_url: &str) -> Result<Url, CapServiceError> {
+    // Without the trailing slash Url::join would replace the last segment of the base.
+    let base_url = if base_url.ends_with('/') {
+        base_url.to_string()
+    } else {
+        format!("{}/", base_url)
+    };
+    Url::parse(&base_url).map_err(|e| CapServiceError::Config(e.to_string()))
+}
+
+fn parse_headers(headers: &[(String, String)]) -> Result<HeaderMap, CapServiceError> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| CapServiceError::Config(e.to_string()))?;
+        let value =
+            HeaderValue::from_str(value).map_err(|e| CapServiceError::Config(e.to_string()))?;
+        map.append(name, value);
+    }
+    Ok(map)
+}
+
+#[async_trait]
+impl ServiceBackend for WebService {
+    type Config = WebServiceConfig;
+    type Error = CapServiceError;
+
+    async fn build(conf: Self::Config) -> Result<Self, Self::Error> {
+        let mut builder = Client::builder()
+            .default_headers(parse_headers(&conf.headers)?)
+            .danger_accept_invalid_certs(conf.accept_invalid_certs);
+        if let Some(timeout) = conf.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = conf.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(user_agent) = &conf.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        for pem in &conf.root_certificates {
+            builder = builder.add_root_certificate(Certificate::from_pem(pem)?);
+        }
+        let base_url = match &conf.base_url {
+            Some(base_url) => Some(parse_base_url(base_url)?),
+            None => None,
+        };
+        let token = match (conf.token, &conf.token_env) {
+            (TokenSource::None, Some(name)) => match std::env::var(name) {
+                Ok(token) => TokenSource::Static(token),
+                Err(_) => {
+                    return Err(CapServiceError::Config(format!(
+                        "token_env {} is not set",
+                        name
+                    )))
+                }
+            },
+            (token, _) => token,
+        };
+
+        Ok(WebService {
+            client: builder.build()?,
+            base_url,
+            token,
+        })
+    }
+
+    /// A HEAD request to the base URL, any answer but a server error will do.
+    async fn health(&self) -> Result<(), Self::Error> {
+        let base_url = match &self.base_url {
+            Some(base_url) => base_url.clone(),
+            None => return Ok(()),
+        };
+        let response = self.client.head(base_url).send().await?;
+        if response.status().is_server_error() {
+            response.error_for_status()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn join_paths_onto_base_url() {
+        let service = WebService {
+            client: Client::new(),
+            base_url: Some(parse_base_url("http://localhost:8080/api").unwrap()),
+            token: TokenSource::None,
+        };
+        assert_eq!(
+            service.url("/orders/1").unwrap().as_str(),
+            "http://localhost:8080/api/orders/1"
+        );
+        assert_eq!(
+            service.url("https://example.com/x").unwrap().as_str(),
+            "https://example.com/x"
+        );
+    }
+
+    #[tokio::test]
+    async fn attach_bearer_token() {
+        let service = WebService {
+            client: Client::new(),
+            base_url: Some(parse_base_url("http://localhost:8080").unwrap()),
+            token: TokenSource::None,
+        };
+        let request = service.authed_get("orders").await.unwrap().build().unwrap();
+        assert!(request.headers().get("authorization").is_none());
+
+        let service = service.with_token(TokenSource::Static("secret".to_string()));
+        let request = service.authed_get("orders").await.unwrap().build().unwrap();
+        assert_eq!(request.headers()["authorization"], "Bearer secret");
+    }
+
+    #[tokio::test]
+    async fn missing_token_env_fails_build() {
+        let config = WebServiceConfig::new().token_env("CAPABILITIES_TEST_UNSET_TOKEN");
+        let error = WebService::build(config).await.unwrap_err();
+        assert!(matches!(
+            error,
+            CapServiceError::Config(e) if e == "token_env CAPABILITIES_TEST_UNSET_TOKEN is not set"
+        ));
+    }
+}
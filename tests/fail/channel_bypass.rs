@@ -0,0 +1,30 @@
+use capabilities::{Authorized, ChannelService, Publish};
+use capabilities_derive::capabilities;
+use capabilities_derive::service;
+
+#[capabilities(Publish, Consume)]
+pub struct InvoiceEvent {
+    #[allow(dead_code)]
+    invoice: i32,
+}
+
+#[service(ChannelService, name = "queues")]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build(16)
+        .await
+        .expect("Failed to setup channels");
+
+    // Only the guard of a capability fn checks a capability, so the queue can't be reached
+    // around it.
+    let authorized = Authorized(Capability::Publish);
+    let event = Publish {
+        data: InvoiceEvent { invoice: 1 },
+    };
+    let _ = service.queues.publish(event, authorized).await;
+    let event = Publish {
+        data: InvoiceEvent { invoice: 1 },
+    };
+    let _ = service.queues.publish(event).await;
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use capabilities::{MockService, Read};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+
+#[capabilities(Read, id = "id")]
+pub struct Orders {
+    #[allow(dead_code)]
+    id: i32,
+}
+
+#[service(MockService)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build().await.expect("Failed to setup service");
+
+    // Only the guard of a capability fn can perform, so there is no way around the capability.
+    let authorized = capabilities::Authorized::check(Capability::Read, Capability::Read)
+        .expect("Failed to authorize");
+    let guard = __capabilities::Guard(authorized);
+    let _ = CapabilityTrait::perform(
+        &service,
+        Read {
+            data: OrdersId { id: 1 },
+        },
+        guard,
+    )
+    .await;
+    Ok(())
+}
+
+#[capability(Read, Orders)]
+fn read_order(order: Orders) -> Result<Orders, CapServiceError> {
+    Ok(order)
+}
+
+#[capability(Read, Orders, id = "i32")]
+fn read_order_by_id(order_id: OrdersId) -> Result<Orders, CapServiceError> {
+    Ok(Orders { id: order_id.id })
+}
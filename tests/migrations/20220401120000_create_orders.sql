@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS orders (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL
+);
@@ -0,0 +1,95 @@
+use capabilities::SqliteDb;
+use capabilities::{Delete, Dialect, Read, TableSchema, Update};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+
+#[capabilities(Read, Update, Delete, id = ("tenant_id", "order_no"), table = "orders")]
+#[allow(dead_code)]
+pub struct Orders {
+    tenant_id: i32,
+    order_no: i64,
+    name: String,
+}
+
+#[service(SqliteDb, name = "db", max_connections = 1)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    assert_eq!(
+        Orders::create_table(Dialect::Sqlite),
+        "CREATE TABLE IF NOT EXISTS orders (\n    tenant_id INTEGER NOT NULL,\n    order_no INTEGER NOT NULL,\n    name TEXT NOT NULL,\n    PRIMARY KEY (tenant_id, order_no)\n);"
+    );
+
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+    sqlx::query(Orders::create_table(Dialect::Sqlite))
+        .execute(&service.db)
+        .await
+        .expect("Failed to create table");
+    sqlx::query("INSERT INTO orders (tenant_id, order_no, name) VALUES (1, 7, 'First'), (2, 7, 'Other tenant')")
+        .execute(&service.db)
+        .await
+        .expect("Failed to insert orders");
+
+    let id = OrdersId { tenant_id: 2, order_no: 7 };
+    let order = read_order_by_id(&service, id, Capability::Read)
+        .await
+        .expect("Failed to read order");
+    assert_eq!(order.name, "Other tenant");
+
+    let id = OrdersId { tenant_id: 1, order_no: 7 };
+    delete_order_by_id(&service, id, Capability::Delete)
+        .await
+        .expect("Failed to delete order");
+    let id = OrdersId { tenant_id: 1, order_no: 7 };
+    let deleted = read_order_by_id(&service, id, Capability::Read).await;
+    assert!(matches!(deleted, Err(CapServiceError::NotFound)));
+
+    Ok(())
+}
+
+#[capability(Read, Orders)]
+fn read_order(order: Orders) -> Result<Orders, CapServiceError> {
+    Ok(order)
+}
+
+#[capability(Read, Orders, id = ("i32", "i64"))]
+fn read_order_by_id(order_id: OrdersId) -> Result<Orders, CapServiceError> {
+    let (name,): (String,) =
+        sqlx::query_as("SELECT name FROM orders WHERE tenant_id = ? AND order_no = ?")
+            .bind(order_id.tenant_id)
+            .bind(order_id.order_no)
+            .fetch_one(&self.db)
+            .await?;
+    Ok(Orders {
+        tenant_id: order_id.tenant_id,
+        order_no: order_id.order_no,
+        name,
+    })
+}
+
+#[capability(Update, Orders)]
+fn update_order(_order: Orders) -> Result<(), CapServiceError> {
+    Ok(())
+}
+
+#[capability(Update, Orders, id = ("i32", "i64"))]
+fn update_order_by_id(_order_id: OrdersId) -> Result<(), CapServiceError> {
+    Ok(())
+}
+
+#[capability(Delete, Orders)]
+fn delete_order(_order: Orders) -> Result<(), CapServiceError> {
+    Ok(())
+}
+
+#[capability(Delete, Orders, id = ("i32", "i64"))]
+fn delete_order_by_id(order_id: OrdersId) -> Result<(), CapServiceError> {
+    sqlx::query("DELETE FROM orders WHERE tenant_id = ? AND order_no = ?")
+        .bind(order_id.tenant_id)
+        .bind(order_id.order_no)
+        .execute(&self.db)
+        .await?;
+    Ok(())
+}
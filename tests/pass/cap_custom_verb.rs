@@ -0,0 +1,88 @@
+use capabilities::SqliteDb;
+use capabilities::{register_verb, Read, Verb};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+
+pub struct ApprovalNote {
+    by: String,
+}
+
+#[capabilities(Read, Approve(input = ApprovalNote, output = Invoice), Archive)]
+pub struct Invoice {
+    id: i32,
+    approved_by: Option<String>,
+    archived: bool,
+}
+
+#[service(SqliteDb, name = "db", max_connections = 1)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+
+    let approve = Capability::Custom(Verb::new("approve"));
+    let note = ApprovalNote {
+        by: "ada".to_string(),
+    };
+    // No token grants a verb before it is registered.
+    let unregistered = approve_invoice(&service, note, approve).await;
+    assert!(matches!(unregistered, Err(CapServiceError::Config(_))));
+
+    Invoice::register_verbs();
+    let note = ApprovalNote {
+        by: "ada".to_string(),
+    };
+    let invoice = approve_invoice(&service, note, approve)
+        .await
+        .expect("Failed to approve invoice");
+    assert_eq!(invoice.approved_by.as_deref(), Some("ada"));
+
+    let note = ApprovalNote {
+        by: "bob".to_string(),
+    };
+    let denied = approve_invoice(&service, note, Capability::Update).await;
+    assert!(matches!(
+        denied,
+        Err(CapServiceError::Denied { required, .. }) if required == approve
+    ));
+
+    // Tokens grant registered verbs, and only those deserialize.
+    let archive = Capability::Custom(register_verb("archive"));
+    let json = serde_json::to_string(&archive).expect("Failed to serialize");
+    assert_eq!(json, r#"{"Custom":"archive"}"#);
+    let parsed: Capability = serde_json::from_str(&json).expect("Failed to deserialize");
+    assert_eq!(parsed, archive);
+
+    let invoice = archive_invoice(&service, invoice, parsed)
+        .await
+        .expect("Failed to archive invoice");
+    assert!(invoice.archived);
+    let denied = archive_invoice(&service, invoice, approve).await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+
+    Ok(())
+}
+
+#[capability(Read, Invoice)]
+fn read_invoice(invoice: Invoice) -> Result<Invoice, CapServiceError> {
+    Ok(invoice)
+}
+
+#[capability(Approve, Invoice)]
+fn approve_invoice(note: ApprovalNote) -> Result<Invoice, CapServiceError> {
+    Ok(Invoice {
+        id: 1,
+        approved_by: Some(note.by),
+        archived: false,
+    })
+}
+
+#[capability(Archive, Invoice)]
+fn archive_invoice(invoice: Invoice) -> Result<Invoice, CapServiceError> {
+    Ok(Invoice {
+        archived: true,
+        ..invoice
+    })
+}
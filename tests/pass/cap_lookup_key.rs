@@ -0,0 +1,109 @@
+use capabilities::SqliteDb;
+use capabilities::{Delete, Read};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+
+#[capabilities(Read, Delete, id = "id", key = "email")]
+#[allow(dead_code)]
+pub struct Person {
+    id: i32,
+    email: String,
+}
+
+// Only needs to look people up by their email.
+async fn find<S>(service: &S, email: &str) -> Result<Person, CapServiceError>
+where
+    S: CapReadPersonByEmail,
+{
+    let key = PersonByEmail {
+        email: email.to_string(),
+    };
+    read_person_by_email(service, key, Capability::Read).await
+}
+
+#[service(SqliteDb, name = "db", max_connections = 1)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+    sqlx::query("CREATE TABLE people (id INTEGER PRIMARY KEY, email TEXT NOT NULL UNIQUE)")
+        .execute(&service.db)
+        .await
+        .expect("Failed to create table");
+    sqlx::query("INSERT INTO people (id, email) VALUES (1, 'ada@example.com')")
+        .execute(&service.db)
+        .await
+        .expect("Failed to insert person");
+
+    let person = find(&service, "ada@example.com")
+        .await
+        .expect("Failed to find person");
+    assert_eq!(person.id, 1);
+
+    let key = PersonByEmail {
+        email: "ada@example.com".to_string(),
+    };
+    let denied = delete_person_by_email(&service, key, Capability::Read).await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+
+    let key = PersonByEmail {
+        email: "ada@example.com".to_string(),
+    };
+    delete_person_by_email(&service, key, Capability::Delete)
+        .await
+        .expect("Failed to delete person");
+    let missing = find(&service, "ada@example.com").await;
+    assert!(matches!(missing, Err(CapServiceError::NotFound)));
+
+    Ok(())
+}
+
+#[capability(Read, Person)]
+fn read_person(person: Person) -> Result<Person, CapServiceError> {
+    Ok(person)
+}
+
+#[capability(Read, Person, id = "i32")]
+fn read_person_by_id(person_id: PersonId) -> Result<Person, CapServiceError> {
+    let (email,): (String,) = sqlx::query_as("SELECT email FROM people WHERE id = ?")
+        .bind(person_id.id)
+        .fetch_one(&self.db)
+        .await?;
+    Ok(Person {
+        id: person_id.id,
+        email,
+    })
+}
+
+#[capability(Read, Person, key = "email")]
+fn read_person_by_email(key: PersonByEmail) -> Result<Person, CapServiceError> {
+    let (id,): (i32,) = sqlx::query_as("SELECT id FROM people WHERE email = ?")
+        .bind(&key.email)
+        .fetch_one(&self.db)
+        .await?;
+    Ok(Person {
+        id,
+        email: key.email,
+    })
+}
+
+#[capability(Delete, Person)]
+fn delete_person(_person: Person) -> Result<(), CapServiceError> {
+    Ok(())
+}
+
+#[capability(Delete, Person, id = "i32")]
+fn delete_person_by_id(_person_id: PersonId) -> Result<(), CapServiceError> {
+    Ok(())
+}
+
+#[capability(Delete, Person, key = "email")]
+fn delete_person_by_email(key: PersonByEmail) -> Result<(), CapServiceError> {
+    sqlx::query("DELETE FROM people WHERE email = ?")
+        .bind(&key.email)
+        .execute(&self.db)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use capabilities::SqliteDb;
+use capabilities::{Dialect, Read, TableSchema};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+
+#[capabilities(Read, id = "id", table = "orders")]
+#[allow(dead_code)]
+pub struct Orders {
+    id: i32,
+    name: String,
+    amount: f64,
+    note: Option<String>,
+}
+
+#[service(SqliteDb, name = "db", max_connections = 1)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    assert_eq!(Orders::TABLE, "orders");
+    assert_eq!(
+        Orders::create_table(Dialect::Postgres),
+        "CREATE TABLE IF NOT EXISTS orders (\n    id INTEGER NOT NULL,\n    name TEXT NOT NULL,\n    amount DOUBLE PRECISION NOT NULL,\n    note TEXT,\n    PRIMARY KEY (id)\n);"
+    );
+
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+
+    sqlx::query(Orders::create_table(Dialect::Sqlite))
+        .execute(&service.db)
+        .await
+        .expect("Generated schema is not valid SQLite");
+
+    sqlx::query("INSERT INTO orders (id, name, amount) VALUES (1, 'Schema order', 9.5)")
+        .execute(&service.db)
+        .await
+        .expect("Failed to insert into generated table");
+
+    Ok(())
+}
+
+#[capability(Read, Orders)]
+fn read_order(order: Orders) -> Result<Orders, CapServiceError> {
+    Ok(order)
+}
+
+#[capability(Read, Orders, id = "i32")]
+fn read_order_by_id(order_id: OrdersId) -> Result<Orders, CapServiceError> {
+    Ok(Orders {
+        id: order_id.id,
+        name: "Schema order".to_string(),
+        amount: 9.5,
+        note: None,
+    })
+}
@@ -0,0 +1,169 @@
+use capabilities::SqliteDb;
+use capabilities::{Create, Delete, Read, ReadAll};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+
+#[capabilities(Read, id = "0")]
+pub struct Sku(String, u32);
+
+#[capabilities(Delete, id = ("0", "1"))]
+#[allow(dead_code)]
+pub struct Slot(i32, i32, String);
+
+#[capabilities(Create, Read, ReadAll, id = "id")]
+pub struct Tagged<T> {
+    id: i32,
+    value: T,
+}
+
+// The id is generic, so `WrapperId<T>` is too.
+#[capabilities(Read, id = "0")]
+pub struct Wrapper<T>(T);
+
+#[capabilities(Create, ReadAll)]
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    Opened(i32),
+    Closed,
+}
+
+// Works for any service that can create any kind of tagged value.
+async fn store<S, T>(service: &S, tagged: Tagged<T>) -> Result<Tagged<T>, CapServiceError>
+where
+    S: CapCreateTagged<T>,
+    T: Send + Sync + 'static,
+{
+    create_tagged(service, tagged, Capability::Create).await
+}
+
+#[service(SqliteDb, name = "db", max_connections = 1)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+
+    let sku = read_sku_by_id(
+        &service,
+        SkuId {
+            id: "A-1".to_string(),
+        },
+        Capability::Read,
+    )
+    .await
+    .expect("Failed to read sku");
+    assert_eq!(sku.0, "A-1");
+    assert_eq!(sku.1, 3);
+
+    delete_slot_by_id(&service, SlotId { _0: 1, _1: 2 }, Capability::Delete)
+        .await
+        .expect("Failed to delete slot");
+
+    let tagged = Tagged {
+        id: 1,
+        value: vec![1u8, 2],
+    };
+    let tagged = store(&service, tagged)
+        .await
+        .expect("Failed to store bytes");
+    assert_eq!(tagged.value, vec![1, 2]);
+    let tagged = Tagged { id: 2, value: 'x' };
+    let tagged = store(&service, tagged).await.expect("Failed to store char");
+    assert_eq!(tagged.value, 'x');
+
+    let tagged = read_tagged_by_id(&service, TaggedId { id: 3 }, Capability::Read)
+        .await
+        .expect("Failed to read tagged");
+    assert_eq!(tagged.value, "three");
+    let all = read_all_tagged(&service, Capability::ReadAll)
+        .await
+        .expect("Failed to read all tagged");
+    assert_eq!(all.len(), 1);
+    let denied = read_all_tagged(&service, Capability::Read).await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+
+    let wrapper = read_wrapper_by_id(&service, WrapperId { id: 7u8 }, Capability::Read)
+        .await
+        .expect("Failed to read wrapper");
+    assert_eq!(wrapper.0, 7);
+
+    let event = create_event(&service, Event::Opened(1), Capability::Create)
+        .await
+        .expect("Failed to create event");
+    assert_eq!(event, Event::Opened(1));
+    let events = read_all_event(&service, Capability::ReadAll)
+        .await
+        .expect("Failed to read events");
+    assert_eq!(events, vec![Event::Closed]);
+
+    Ok(())
+}
+
+#[capability(Read, Sku)]
+fn read_sku(sku: Sku) -> Result<Sku, CapServiceError> {
+    Ok(sku)
+}
+
+#[capability(Read, Sku, id = "String")]
+fn read_sku_by_id(sku_id: SkuId) -> Result<Sku, CapServiceError> {
+    Ok(Sku(sku_id.id, 3))
+}
+
+#[capability(Delete, Slot)]
+fn delete_slot(_slot: Slot) -> Result<(), CapServiceError> {
+    Ok(())
+}
+
+#[capability(Delete, Slot, id = ("i32", "i32"))]
+fn delete_slot_by_id(slot_id: SlotId) -> Result<(), CapServiceError> {
+    assert_eq!((slot_id._0, slot_id._1), (1, 2));
+    Ok(())
+}
+
+#[capability(Create, Tagged)]
+fn create_tagged<T: Send + Sync + 'static>(
+    tagged: Tagged<T>,
+) -> Result<Tagged<T>, CapServiceError> {
+    Ok(tagged)
+}
+
+#[capability(Read, Tagged, id = "i32")]
+fn read_tagged_by_id(tagged_id: TaggedId) -> Result<Tagged<String>, CapServiceError> {
+    Ok(Tagged {
+        id: tagged_id.id,
+        value: "three".to_string(),
+    })
+}
+
+#[capability(ReadAll, Tagged)]
+fn read_all_tagged() -> Result<Vec<Tagged<String>>, CapServiceError> {
+    Ok(vec![Tagged {
+        id: 3,
+        value: "three".to_string(),
+    }])
+}
+
+#[capability(Read, Wrapper)]
+fn read_wrapper<T: Send + Sync + 'static>(
+    wrapper: Wrapper<T>,
+) -> Result<Wrapper<T>, CapServiceError> {
+    Ok(wrapper)
+}
+
+#[capability(Read, Wrapper, id = "T")]
+fn read_wrapper_by_id<T: Send + Sync + 'static>(
+    wrapper_id: WrapperId<T>,
+) -> Result<Wrapper<T>, CapServiceError> {
+    Ok(Wrapper(wrapper_id.id))
+}
+
+#[capability(Create, Event)]
+fn create_event(event: Event) -> Result<Event, CapServiceError> {
+    Ok(event)
+}
+
+#[capability(ReadAll, Event)]
+fn read_all_event() -> Result<Vec<Event>, CapServiceError> {
+    Ok(vec![Event::Closed])
+}
@@ -0,0 +1,39 @@
+use capabilities::ServiceBackend;
+use capabilities_derive::service;
+
+#[derive(Clone)]
+pub struct BucketStore {
+    bucket: String,
+}
+
+#[async_trait]
+impl ServiceBackend for BucketStore {
+    type Config = String;
+    type Error = std::io::Error;
+
+    async fn build(conf: Self::Config) -> Result<Self, Self::Error> {
+        Ok(BucketStore { bucket: conf })
+    }
+
+    async fn health(&self) -> Result<(), Self::Error> {
+        if self.bucket.is_empty() {
+            Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No bucket"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[service(BucketStore, name = "store")]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build("orders".to_string())
+        .await
+        .expect("Failed to setup service");
+
+    assert!(service.health().await.is_ok());
+    assert_eq!(service.store.bucket, "orders");
+
+    service.close().await;
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use capabilities::ChannelService;
+use capabilities_derive::capabilities;
+use capabilities_derive::service;
+
+#[capabilities(Publish, Consume)]
+pub struct InvoiceEvent {
+    #[allow(dead_code)]
+    invoice: i32,
+    #[allow(dead_code)]
+    amount: i64,
+}
+
+#[service(ChannelService, name = "queues")]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build(16).await.expect("Failed to setup channels");
+
+    let event = InvoiceEvent {
+        invoice: 1,
+        amount: 250,
+    };
+    publish_invoice_event(&service, event, Capability::Publish)
+        .await
+        .expect("Failed to publish event");
+
+    let denied = consume_invoice_event(&service, Capability::Publish).await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+
+    let event = consume_invoice_event(&service, Capability::Consume)
+        .await
+        .expect("Failed to consume event");
+    assert_eq!(event.invoice, 1);
+    assert_eq!(event.amount, 250);
+
+    let denied = publish_invoice_event(
+        &service,
+        InvoiceEvent {
+            invoice: 2,
+            amount: 0,
+        },
+        Capability::Consume,
+    )
+    .await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+
+    Ok(())
+}
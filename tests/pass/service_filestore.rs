@@ -0,0 +1,67 @@
+use capabilities::{Create, Delete, FileStore, Read, ReadAll, Update};
+use capabilities_derive::capabilities;
+use capabilities_derive::service;
+use serde::{Deserialize, Serialize};
+
+#[capabilities(Create, Read, Update, Delete, ReadAll, id = "id", collection = "orders")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Orders {
+    id: i32,
+    name: String,
+}
+
+#[service(FileStore, name = "store")]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let root = std::env::temp_dir().join("capabilities_service_filestore");
+    let _ = std::fs::remove_dir_all(&root);
+    let service = CapService::build(&root).await.expect("Failed to setup store");
+
+    let order = Orders {
+        id: 1,
+        name: "Filed order".to_string(),
+    };
+    service
+        .guarded_perform(Create { data: order }, Capability::Create)
+        .await
+        .expect("Failed to create order");
+    assert!(root.join("orders").join("1.json").is_file());
+
+    let order = service
+        .guarded_perform(Read { data: OrdersId { id: 1 } }, Capability::Read)
+        .await
+        .expect("Failed to read order");
+    assert_eq!(order.name, "Filed order");
+
+    let renamed = Orders {
+        id: 1,
+        name: "Renamed order".to_string(),
+    };
+    service
+        .guarded_perform(Update { data: renamed }, Capability::Update)
+        .await
+        .expect("Failed to update order");
+
+    let orders = service
+        .guarded_perform(ReadAll { data: Vec::<Orders>::new() }, Capability::ReadAll)
+        .await
+        .expect("Failed to read orders");
+    assert_eq!(orders.len(), 1);
+    assert_eq!(orders[0].name, "Renamed order");
+
+    let denied = service
+        .guarded_perform(Delete { data: OrdersId { id: 1 } }, Capability::Read)
+        .await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+
+    service
+        .guarded_perform(Delete { data: OrdersId { id: 1 } }, Capability::Delete)
+        .await
+        .expect("Failed to delete order");
+    let missing = service
+        .guarded_perform(Read { data: OrdersId { id: 1 } }, Capability::Read)
+        .await;
+    assert!(matches!(missing, Err(CapServiceError::NotFound)));
+
+    std::fs::remove_dir_all(root)
+}
@@ -0,0 +1,45 @@
+use capabilities::{Create, FileStore, Read};
+use capabilities_derive::capabilities;
+use capabilities_derive::service;
+use serde::{Deserialize, Serialize};
+
+#[capabilities(Create, Read, id = ("tenant", "code"), collection = "orders")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Orders {
+    tenant: String,
+    code: String,
+    name: String,
+}
+
+#[service(FileStore, name = "store")]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let root = std::env::temp_dir().join("capabilities_service_filestore_composite");
+    let _ = std::fs::remove_dir_all(&root);
+    let service = CapService::build(&root).await.expect("Failed to setup store");
+
+    // Joined by `_` as they are, both ids would be the key "a_b_c".
+    for (tenant, code) in [("a_b", "c"), ("a", "b_c")] {
+        let order = Orders {
+            tenant: tenant.to_string(),
+            code: code.to_string(),
+            name: format!("{} {}", tenant, code),
+        };
+        service
+            .guarded_perform(Create { data: order }, Capability::Create)
+            .await
+            .expect("Failed to create order");
+    }
+
+    let id = OrdersId {
+        tenant: "a".to_string(),
+        code: "b_c".to_string(),
+    };
+    let order = service
+        .guarded_perform(Read { data: id }, Capability::Read)
+        .await
+        .expect("Failed to read order");
+    assert_eq!(order.name, "a b_c");
+
+    std::fs::remove_dir_all(root)
+}
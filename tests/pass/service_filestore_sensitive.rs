@@ -0,0 +1,56 @@
+use capabilities::{Create, FileStore, Grant, Read, ReadAll};
+use capabilities_derive::capabilities;
+use capabilities_derive::service;
+use serde::{Deserialize, Serialize};
+
+#[capabilities(Create, Read, ReadAll, id = "id", collection = "person")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Person {
+    id: i64,
+    name: String,
+    #[cap(sensitive)]
+    personnummer: String,
+}
+
+#[service(FileStore, name = "store")]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let root = std::env::temp_dir().join("capabilities_service_filestore_sensitive");
+    let _ = std::fs::remove_dir_all(&root);
+    let service = CapService::build(&root).await.expect("Failed to setup store");
+
+    let person = Person {
+        id: 1,
+        name: "Ada".to_string(),
+        personnummer: "19151210-1234".to_string(),
+    };
+    service
+        .guarded_perform(Create { data: person }, Capability::Create)
+        .await
+        .expect("Failed to create person");
+
+    // Without the grant the field is left out, like a read of a database service.
+    let person: PersonView = service
+        .guarded_perform(Read { data: PersonId { id: 1 } }, Capability::Read)
+        .await
+        .expect("Failed to read person");
+    assert_eq!(person.name, "Ada");
+    assert_eq!(person.personnummer, None);
+    let json = serde_json::to_string(&person).expect("Failed to serialize person");
+    assert_eq!(json, r#"{"id":1,"name":"Ada"}"#);
+
+    let people = service
+        .guarded_perform(ReadAll { data: Vec::<Person>::new() }, Capability::ReadAll)
+        .await
+        .expect("Failed to read people");
+    assert!(people[0].personnummer.is_none());
+
+    let elevated = Grant::new(Capability::Read).with_read_field("person", "personnummer");
+    let person = service
+        .guarded_perform(Read { data: PersonId { id: 1 } }, elevated)
+        .await
+        .expect("Failed to read person");
+    assert_eq!(person.personnummer.as_deref(), Some("19151210-1234"));
+
+    std::fs::remove_dir_all(root)
+}
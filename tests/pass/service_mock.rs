@@ -0,0 +1,67 @@
+use capabilities::{Delete, MockService, Read};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+
+#[capabilities(Read, Delete, id = "id")]
+pub struct Orders {
+    #[allow(dead_code)]
+    id: i32,
+    #[allow(dead_code)]
+    name: String,
+}
+
+#[service(MockService)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build().await.expect("Failed to setup service");
+
+    service.mock().script::<Read<OrdersId>, Orders>(Ok(Orders {
+        id: 1,
+        name: "Scripted order".to_string(),
+    }));
+    let order = read_order_by_id(&service, OrdersId { id: 1 }, Capability::Read)
+        .await
+        .expect("Failed to read order");
+    assert_eq!(order.name, "Scripted order");
+
+    // Without a script the body runs.
+    let order = read_order_by_id(&service, OrdersId { id: 2 }, Capability::Read)
+        .await
+        .expect("Failed to read order");
+    assert_eq!(order.name, "Order from the body");
+
+    let denied = delete_order_by_id(&service, OrdersId { id: 1 }, Capability::Read).await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+
+    let mock = service.mock();
+    assert_eq!(mock.performed::<Read<OrdersId>>(), 2);
+    assert_eq!(mock.denied::<Delete<OrdersId>>(), 1);
+    assert_eq!(mock.performed::<Delete<OrdersId>>(), 0);
+    mock.assert_never_performed_without(Capability::Delete);
+
+    Ok(())
+}
+
+#[capability(Read, Orders)]
+fn read_order(order: Orders) -> Result<Orders, CapServiceError> {
+    Ok(order)
+}
+
+#[capability(Read, Orders, id = "i32")]
+fn read_order_by_id(order_id: OrdersId) -> Result<Orders, CapServiceError> {
+    Ok(Orders {
+        id: order_id.id,
+        name: "Order from the body".to_string(),
+    })
+}
+
+#[capability(Delete, Orders)]
+fn delete_order(_order: Orders) -> Result<(), CapServiceError> {
+    Ok(())
+}
+
+#[capability(Delete, Orders, id = "i32")]
+fn delete_order_by_id(_order_id: OrdersId) -> Result<(), CapServiceError> {
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use capabilities::SqliteDb;
+use capabilities_derive::service;
+
+#[service(
+    SqliteDb,
+    name = "db",
+    max_connections = 4,
+    min_connections = 1,
+    acquire_timeout = 5,
+    idle_timeout = 60,
+    wal = true,
+    foreign_keys = true
+)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let builder = CapService::builder();
+    assert_eq!(builder.config().max_connections, Some(4));
+    assert_eq!(builder.config().min_connections, Some(1));
+    assert_eq!(builder.config().acquire_timeout, Some(Duration::from_secs(5)));
+    assert_eq!(builder.config().idle_timeout, Some(Duration::from_secs(60)));
+    assert!(builder.config().wal);
+    assert_eq!(builder.config().foreign_keys, Some(true));
+
+    let service = CapService::builder()
+        .url("sqlite::memory:")
+        .max_connections(1)
+        .build()
+        .await
+        .expect("Failed to create database");
+    assert_eq!(service.db.size(), 1);
+
+    let _lazy = CapService::builder()
+        .url("sqlite::memory:")
+        .lazy(true)
+        .build()
+        .await
+        .expect("Failed to create lazy pool");
+
+    std::env::set_var("DATABASE_URL", "sqlite::memory:");
+    let _from_env = CapService::build_from_env()
+        .await
+        .expect("Failed to create database from DATABASE_URL");
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use capabilities::Read;
+use capabilities::WebService;
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+
+#[capabilities(Read, id = "id")]
+pub struct Orders {
+    #[allow(dead_code)]
+    id: i32,
+    #[allow(dead_code)]
+    name: String,
+}
+
+#[service(
+    WebService,
+    name = "api",
+    base_url = "http://localhost:8080/api",
+    timeout = 10,
+    user_agent = "capabilities-test",
+    header = "X-Tenant: acme"
+)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let config = CapService::config();
+    assert_eq!(config.timeout, Some(Duration::from_secs(10)));
+    assert_eq!(config.user_agent.as_deref(), Some("capabilities-test"));
+    assert_eq!(config.headers, vec![("X-Tenant".to_string(), "acme".to_string())]);
+
+    let service = CapService::build().await.expect("Failed to setup service");
+    assert_eq!(
+        service.url("orders").expect("Missing base url").as_str(),
+        "http://localhost:8080/api/orders"
+    );
+
+    let order = read_order_by_id(&service, OrdersId { id: 7 }, Capability::Read)
+        .await
+        .expect("Failed to read order");
+    assert_eq!(order.name, "http://localhost:8080/api/orders/7");
+
+    Ok(())
+}
+
+#[capability(Read, Orders)]
+fn read_order(order: Orders) -> Result<Orders, CapServiceError> {
+    Ok(order)
+}
+
+#[capability(Read, Orders, id = "i32")]
+fn read_order_by_id(order_id: OrdersId) -> Result<Orders, CapServiceError> {
+    // No server to talk to, so hand back the URL the request would go to.
+    let url = self.url(&format!("orders/{}", order_id.id))?;
+    Ok(Orders {
+        id: order_id.id,
+        name: url.to_string(),
+    })
+}
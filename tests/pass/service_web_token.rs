@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use capabilities::Read;
+use capabilities::WebService;
+use capabilities::{AccessToken, TokenSource};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+
+#[capabilities(Read, id = "id")]
+pub struct Orders {
+    #[allow(dead_code)]
+    id: i32,
+    #[allow(dead_code)]
+    name: String,
+}
+
+#[service(
+    WebService,
+    name = "api",
+    base_url = "http://localhost:8080/api",
+    token_env = "ORDERS_API_TOKEN"
+)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let unset = CapService::build().await;
+    assert!(matches!(unset, Err(CapServiceError::Config(_))));
+
+    std::env::set_var("ORDERS_API_TOKEN", "static-token");
+    let service = CapService::build().await.expect("Failed to setup service");
+    let order = read_order_by_id(&service, OrdersId { id: 7 }, Capability::Read)
+        .await
+        .expect("Failed to read order");
+    assert_eq!(order.name, "Bearer static-token");
+
+    let service = service.with_token(TokenSource::provider(|| async {
+        Ok(AccessToken::new("granted-token").expires_in(Duration::from_secs(300)))
+    }));
+    let order = read_order_by_id(&service, OrdersId { id: 7 }, Capability::Read)
+        .await
+        .expect("Failed to read order");
+    assert_eq!(order.name, "Bearer granted-token");
+
+    Ok(())
+}
+
+#[capability(Read, Orders)]
+fn read_order(order: Orders) -> Result<Orders, CapServiceError> {
+    Ok(order)
+}
+
+#[capability(Read, Orders, id = "i32")]
+fn read_order_by_id(order_id: OrdersId) -> Result<Orders, CapServiceError> {
+    // No server to talk to, so hand back the bearer the request would carry.
+    let request = self.api.authed_get(&format!("orders/{}", order_id.id)).await?.build()?;
+    let bearer = request.headers()["authorization"].to_str().unwrap_or_default();
+    Ok(Orders {
+        id: order_id.id,
+        name: bearer.to_string(),
+    })
+}
@@ -0,0 +1,93 @@
+use capabilities::CreateAll;
+use capabilities::SqliteDb;
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+
+#[capabilities(CreateAll, id = "id")]
+pub struct Orders {
+    id: i32,
+    name: String,
+}
+
+#[service(SqliteDb, name = "db", max_connections = 1)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+    sqlx::query("CREATE TABLE orders (id INTEGER PRIMARY KEY, name TEXT NOT NULL)")
+        .execute(&service.db)
+        .await
+        .expect("Failed to create table");
+
+    let batch = vec![
+        Orders {
+            id: 1,
+            name: "First".to_string(),
+        },
+        Orders {
+            id: 2,
+            name: "Second".to_string(),
+        },
+    ];
+    let denied = create_orders(&service, batch, Capability::Create).await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+
+    let batch = vec![
+        Orders {
+            id: 1,
+            name: "First".to_string(),
+        },
+        Orders {
+            id: 2,
+            name: "Second".to_string(),
+        },
+    ];
+    let created = create_orders(&service, batch, Capability::CreateAll)
+        .await
+        .expect("Failed to create orders");
+    assert_eq!(created.len(), 2);
+    assert_eq!(created[1].name, "Second");
+
+    let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM orders")
+        .fetch_one(&service.db)
+        .await
+        .expect("Failed to count orders");
+    assert_eq!(count.0, 2);
+
+    // The second order exists already, so the first one is not kept either.
+    let batch = vec![
+        Orders {
+            id: 3,
+            name: "Third".to_string(),
+        },
+        Orders {
+            id: 2,
+            name: "Second again".to_string(),
+        },
+    ];
+    let conflict = create_orders(&service, batch, Capability::CreateAll).await;
+    assert!(conflict.is_err());
+    let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM orders")
+        .fetch_one(&service.db)
+        .await
+        .expect("Failed to count orders");
+    assert_eq!(count.0, 2);
+    Ok(())
+}
+
+// The batch is created as a whole or not at all.
+#[capability(CreateAll, Orders)]
+fn create_orders(orders: Vec<Orders>) -> Result<Vec<Orders>, CapServiceError> {
+    let mut tx = self.db.begin().await?;
+    for order in &orders {
+        sqlx::query("INSERT INTO orders (id, name) VALUES (?, ?)")
+            .bind(order.id)
+            .bind(&order.name)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(orders)
+}
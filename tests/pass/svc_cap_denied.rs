@@ -0,0 +1,44 @@
+use capabilities::SqliteDb;
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+use capabilities::Create;
+
+#[capabilities(Create, id = "id")]
+pub struct Orders {
+    #[allow(dead_code)]
+    id: i32,
+    #[allow(dead_code)]
+    name: String,
+}
+
+#[service(SqliteDb, name = "db")]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let broken = CapService::build("postgres://".to_string()).await;
+    assert!(broken.is_err());
+
+    let connection_string = "sqlite::memory:".to_string();
+    let pool = CapService::build(connection_string)
+        .await
+        .expect("Failed to create database");
+
+    let order = Orders { id: 1, name: "Not mine to create".to_string()};
+
+    let r = create_order(&pool, order, Capability::Read).await;
+
+    assert!(matches!(
+        r,
+        Err(CapServiceError::Denied {
+            required: Capability::Create,
+            presented: Capability::Read
+        })
+    ));
+
+    Ok(())
+}
+
+#[capability(Create, Orders)]
+fn create_order(order: Orders) -> Result<Orders, CapServiceError> {
+    Ok(order)
+}
@@ -0,0 +1,119 @@
+use capabilities::SqliteDb;
+use capabilities::{Create, Update};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+use serde::{Deserialize, Serialize};
+
+#[capabilities(Create, Update, dto, id = "id", client = "/person")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Person {
+    id: i64,
+    #[serde(rename = "name")]
+    firstname: String,
+    lastname: String,
+    #[cap(skip_create, skip_update)]
+    created_by: String,
+}
+
+#[service(SqliteDb, name = "db", max_connections = 1)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+    sqlx::query(
+        "CREATE TABLE person (id INTEGER PRIMARY KEY AUTOINCREMENT, firstname TEXT NOT NULL, \
+         lastname TEXT NOT NULL, created_by TEXT NOT NULL)",
+    )
+    .execute(&service.db)
+    .await
+    .expect("Failed to create table");
+
+    // The caller no longer invents the id.
+    let new: NewPerson = serde_json::from_str(r#"{"name":"Ada","lastname":"Lovelace"}"#)
+        .expect("Failed to parse new person");
+    let person = create_person(&service, new, Capability::Create)
+        .await
+        .expect("Failed to create person");
+    assert_eq!(person.id, 1);
+    assert_eq!(person.created_by, "service");
+
+    let patch = PersonPatch {
+        id: person.id,
+        lastname: Some("King".to_string()),
+        ..Default::default()
+    };
+    update_person(&service, patch, Capability::Update)
+        .await
+        .expect("Failed to update person");
+    let updated: Person = sqlx::query_as("SELECT * FROM person WHERE id = 1")
+        .fetch_one(&service.db)
+        .await
+        .expect("Failed to read person");
+    assert_eq!(updated.firstname, "Ada");
+    assert_eq!(updated.lastname, "King");
+
+    let denied = update_person(
+        &service,
+        PersonPatch::from(updated.clone()),
+        Capability::Read,
+    )
+    .await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+
+    // An update by id still works next to the patch.
+    retire_person(&service, PersonId { id: 1 }, Capability::Update)
+        .await
+        .expect("Failed to retire person");
+    let (created_by,): (String,) = sqlx::query_as("SELECT created_by FROM person WHERE id = 1")
+        .fetch_one(&service.db)
+        .await
+        .expect("Failed to read person");
+    assert_eq!(created_by, "retired");
+
+    let mut copy = updated.clone();
+    PersonPatch::from(updated.clone()).apply(&mut copy);
+    assert_eq!(copy, updated);
+    let new = NewPerson::from(updated.clone());
+    assert_eq!(new.into_person(1, "service".to_string()), updated);
+    Ok(())
+}
+
+#[capability(Create, Person)]
+fn create_person(new: NewPerson) -> Result<Person, CapServiceError> {
+    let (id,): (i64,) = sqlx::query_as(
+        "INSERT INTO person (firstname, lastname, created_by) VALUES (?, ?, ?) RETURNING id",
+    )
+    .bind(&new.firstname)
+    .bind(&new.lastname)
+    .bind("service")
+    .fetch_one(&self.db)
+    .await?;
+    Ok(new.into_person(id, "service".to_string()))
+}
+
+#[capability(Update, Person)]
+fn update_person(patch: PersonPatch) -> Result<(), CapServiceError> {
+    let mut person: Person = sqlx::query_as("SELECT * FROM person WHERE id = ?")
+        .bind(patch.id)
+        .fetch_one(&self.db)
+        .await?;
+    patch.apply(&mut person);
+    sqlx::query("UPDATE person SET firstname = ?, lastname = ? WHERE id = ?")
+        .bind(&person.firstname)
+        .bind(&person.lastname)
+        .bind(person.id)
+        .execute(&self.db)
+        .await?;
+    Ok(())
+}
+
+#[capability(Update, Person, id = "i64")]
+fn retire_person(person_id: PersonId) -> Result<(), CapServiceError> {
+    sqlx::query("UPDATE person SET created_by = 'retired' WHERE id = ?")
+        .bind(person_id.id)
+        .execute(&self.db)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use capabilities::SqliteDb;
+use capabilities::{Grant, Update};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+use serde::{Deserialize, Serialize};
+
+#[capabilities(Update, dto, id = "id")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Orders {
+    id: i64,
+    #[cap(grant)]
+    status: String,
+    amount: i64,
+}
+
+#[service(SqliteDb, name = "db", max_connections = 1)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+    sqlx::query("CREATE TABLE orders (id INTEGER PRIMARY KEY, status TEXT NOT NULL, amount INTEGER NOT NULL)")
+        .execute(&service.db)
+        .await
+        .expect("Failed to create table");
+    sqlx::query("INSERT INTO orders (id, status, amount) VALUES (1, 'open', 100)")
+        .execute(&service.db)
+        .await
+        .expect("Failed to insert order");
+
+    // A support agent holding `update:orders.status`.
+    let support = Grant::new(Capability::UpdateFields).with_field("orders", "status");
+    let status = OrdersPatch {
+        id: 1,
+        status: Some("shipped".to_string()),
+        ..Default::default()
+    };
+    update_orders(&service, status, support.clone())
+        .await
+        .expect("Failed to update status");
+
+    let amount = OrdersPatch {
+        id: 1,
+        amount: Some(0),
+        ..Default::default()
+    };
+    let denied = update_orders(&service, amount.clone(), support).await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+
+    update_orders(&service, amount, Capability::Update)
+        .await
+        .expect("Failed to update amount");
+    let order: Orders = sqlx::query_as("SELECT * FROM orders WHERE id = 1")
+        .fetch_one(&service.db)
+        .await
+        .expect("Failed to read order");
+    assert_eq!(order.status, "shipped");
+    assert_eq!(order.amount, 0);
+    Ok(())
+}
+
+#[capability(Update, Orders)]
+fn update_orders(patch: OrdersPatch) -> Result<(), CapServiceError> {
+    let mut order: Orders = sqlx::query_as("SELECT * FROM orders WHERE id = ?")
+        .bind(patch.id)
+        .fetch_one(&self.db)
+        .await?;
+    patch.apply(&mut order);
+    sqlx::query("UPDATE orders SET status = ?, amount = ? WHERE id = ?")
+        .bind(&order.status)
+        .bind(order.amount)
+        .bind(order.id)
+        .execute(&self.db)
+        .await?;
+    Ok(())
+}
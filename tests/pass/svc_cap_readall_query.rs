@@ -0,0 +1,86 @@
+use capabilities::SqliteDb;
+use capabilities::{Dialect, ReadAll, SortOrder, TableSchema};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+use serde::Deserialize;
+
+#[capabilities(ReadAll, query, id = "id", table = "orders", client = "/orders")]
+#[derive(Debug, Deserialize, sqlx::FromRow)]
+pub struct Orders {
+    id: i32,
+    name: String,
+    amount: f64,
+    note: Option<String>,
+}
+
+#[service(SqliteDb, name = "db", max_connections = 1)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+    sqlx::query(Orders::create_table(Dialect::Sqlite))
+        .execute(&service.db)
+        .await
+        .expect("Failed to create table");
+    for (id, name) in [
+        (1, "tea"),
+        (2, "coffee"),
+        (3, "tea"),
+        (4, "tea"),
+        (5, "cake"),
+    ] {
+        sqlx::query("INSERT INTO orders (id, name, amount) VALUES (?, ?, ?)")
+            .bind(id)
+            .bind(name)
+            .bind(id as f64 * 1.5)
+            .execute(&service.db)
+            .await
+            .expect("Failed to insert order");
+    }
+
+    let query = OrdersQuery::new()
+        .filter(OrdersFilter {
+            name: Some("tea".to_string()),
+            ..Default::default()
+        })
+        .sort_by(OrdersField::Amount, SortOrder::Desc)
+        .limit(2);
+    let page = read_all_orders(&service, query.clone(), Capability::ReadAll)
+        .await
+        .expect("Failed to read orders");
+    assert_eq!(page.total, 3);
+    assert_eq!(page.items.iter().map(|o| o.id).collect::<Vec<_>>(), [4, 3]);
+    assert_eq!(page.next.as_deref(), Some("2"));
+
+    let next = query.after(page.next.unwrap());
+    let page = read_all_orders(&service, next, Capability::ReadAll)
+        .await
+        .expect("Failed to read next page");
+    assert_eq!(page.items.iter().map(|o| o.id).collect::<Vec<_>>(), [1]);
+    assert_eq!(page.next, None);
+
+    // The REST layer passes the query as parameters.
+    let params = vec![
+        ("sort".to_string(), "-id".to_string()),
+        ("limit".to_string(), "1".to_string()),
+    ];
+    let query = OrdersQuery::from_params(params).expect("Failed to parse query");
+    let page = read_all_orders(&service, query, Capability::ReadAll)
+        .await
+        .expect("Failed to read orders");
+    assert_eq!(page.total, 5);
+    assert_eq!(page.items[0].name, "cake");
+    let unknown = vec![("colour".to_string(), "red".to_string())];
+    assert!(OrdersQuery::from_params(unknown).is_err());
+
+    let denied = read_all_orders(&service, OrdersQuery::new(), Capability::Read).await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+    Ok(())
+}
+
+#[capability(ReadAll, Orders)]
+fn read_all_orders(query: OrdersQuery) -> Result<Page<Orders>, CapServiceError> {
+    query.fetch_page(&self.db).await
+}
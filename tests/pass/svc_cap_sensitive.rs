@@ -0,0 +1,109 @@
+use capabilities::SqliteDb;
+use capabilities::{fetch_stream, Grant, Lease, Read, ReadAll, StreamAll};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+use futures_util::StreamExt;
+use serde::Serialize;
+
+#[capabilities(Read, ReadAll, StreamAll, id = "id")]
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Person {
+    id: i64,
+    name: String,
+    #[cap(sensitive)]
+    personnummer: String,
+}
+
+#[service(SqliteDb, name = "db", max_connections = 1)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+    sqlx::query(
+        "CREATE TABLE person (id INTEGER PRIMARY KEY, name TEXT NOT NULL, \
+         personnummer TEXT NOT NULL)",
+    )
+    .execute(&service.db)
+    .await
+    .expect("Failed to create table");
+    sqlx::query("INSERT INTO person (id, name, personnummer) VALUES (1, 'Ada', '19151210-1234')")
+        .execute(&service.db)
+        .await
+        .expect("Failed to insert person");
+
+    // Without the grant the field is blanked and left out of the JSON.
+    let person: PersonView = read_person_by_id(&service, PersonId { id: 1 }, Capability::Read)
+        .await
+        .expect("Failed to read person");
+    assert_eq!(person.name, "Ada");
+    assert_eq!(person.personnummer, None);
+    let json = serde_json::to_string(&person).expect("Failed to serialize person");
+    assert_eq!(json, r#"{"id":1,"name":"Ada"}"#);
+
+    let elevated = Grant::new(Capability::Read).with_read_field("person", "personnummer");
+    let person = read_person_by_id(&service, PersonId { id: 1 }, elevated)
+        .await
+        .expect("Failed to read person");
+    assert_eq!(person.personnummer.as_deref(), Some("19151210-1234"));
+
+    let people = read_all_person(&service, Capability::ReadAll)
+        .await
+        .expect("Failed to read people");
+    assert_eq!(people.len(), 1);
+    assert!(people[0].personnummer.is_none());
+
+    let elevated = Grant::new(Capability::ReadAll).with_read_field("person", "personnummer");
+    let people = read_all_person(&service, elevated)
+        .await
+        .expect("Failed to read people");
+    assert!(people[0].personnummer.is_some());
+
+    // A stream projects its items with the grant of the lease.
+    let people: Vec<_> = stream_person(&service, Capability::ReadAll)
+        .await
+        .expect("Failed to open stream")
+        .collect()
+        .await;
+    assert!(people[0].as_ref().unwrap().personnummer.is_none());
+
+    let elevated = Grant::new(Capability::ReadAll).with_read_field("person", "personnummer");
+    let people: Vec<_> = stream_person(&service, Lease::new(elevated))
+        .await
+        .expect("Failed to open stream")
+        .collect()
+        .await;
+    assert!(people[0].as_ref().unwrap().personnummer.is_some());
+
+    let denied = read_person_by_id(&service, PersonId { id: 1 }, Capability::Create).await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+    Ok(())
+}
+
+#[capability(Read, Person)]
+fn read_person(person: Person) -> Result<Person, CapServiceError> {
+    Ok(person)
+}
+
+#[capability(Read, Person, id = "i64")]
+fn read_person_by_id(person_id: PersonId) -> Result<Person, CapServiceError> {
+    let person = sqlx::query_as("SELECT * FROM person WHERE id = ?")
+        .bind(person_id.id)
+        .fetch_one(&self.db)
+        .await?;
+    Ok(person)
+}
+
+#[capability(ReadAll, Person)]
+fn read_all_person() -> Result<Vec<Person>, CapServiceError> {
+    let people = sqlx::query_as("SELECT * FROM person")
+        .fetch_all(&self.db)
+        .await?;
+    Ok(people)
+}
+
+#[capability(StreamAll, Person)]
+fn stream_person() -> Result<CapStream<Person>, CapServiceError> {
+    Ok(fetch_stream(&self.db, "SELECT * FROM person"))
+}
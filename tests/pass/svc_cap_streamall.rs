@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use actix_web::body::to_bytes;
+use actix_web::test::TestRequest;
+use actix_web::Responder;
+use capabilities::SqliteDb;
+use capabilities::{fetch_stream, Lease, NdJson, StreamAll};
+use capabilities_derive::capabilities;
+use capabilities_derive::capability;
+use capabilities_derive::service;
+use futures_util::StreamExt;
+use serde::Serialize;
+
+#[capabilities(StreamAll, id = "id")]
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Orders {
+    id: i32,
+    name: String,
+}
+
+#[service(SqliteDb, name = "db", max_connections = 1)]
+#[tokio::main]
+async fn main() -> Result<(), std::io::Error> {
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+    sqlx::query("CREATE TABLE orders (id INTEGER PRIMARY KEY, name TEXT NOT NULL)")
+        .execute(&service.db)
+        .await
+        .expect("Failed to create table");
+    sqlx::query("INSERT INTO orders (id, name) VALUES (1, 'First'), (2, 'Second'), (3, 'Third')")
+        .execute(&service.db)
+        .await
+        .expect("Failed to insert orders");
+
+    let orders: Vec<_> = stream_orders(&service, Capability::ReadAll)
+        .await
+        .expect("Failed to open stream")
+        .collect()
+        .await;
+    assert_eq!(orders.len(), 3);
+    assert_eq!(
+        orders[2].as_ref().expect("Failed to read order").name,
+        "Third"
+    );
+
+    let denied = stream_orders(&service, Capability::Read).await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+
+    let expired = Lease::new(Capability::ReadAll).expires_in(Duration::ZERO);
+    let expired = stream_orders(&service, expired).await;
+    assert!(matches!(expired, Err(CapServiceError::Expired)));
+
+    // One JSON object per line.
+    let stream = stream_orders(&service, Capability::ReadAll)
+        .await
+        .expect("Failed to open stream");
+    let request = TestRequest::default().to_http_request();
+    let response = NdJson(stream).respond_to(&request);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/x-ndjson"
+    );
+    let body = to_bytes(response.into_body())
+        .await
+        .expect("Failed to read body");
+    let lines: Vec<&str> = std::str::from_utf8(&body).unwrap().lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], r#"{"id":1,"name":"First"}"#);
+    Ok(())
+}
+
+#[capability(StreamAll, Orders)]
+fn stream_orders() -> Result<CapStream<Orders>, CapServiceError> {
+    Ok(fetch_stream(&self.db, "SELECT * FROM orders ORDER BY id"))
+}
@@ -0,0 +1,177 @@
+use actix_web::{web, App, HttpResponse, HttpServer};
+use capabilities::{Create, Delete, DeleteAll, Read, Update, WebService, WebServiceConfig};
+use capabilities_derive::{capabilities, capability, service};
+use serde::{Deserialize, Serialize};
+
+#[capabilities(Read, Create, Update, Delete, DeleteAll, id = "id", client = "/orders")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Orders {
+    id: i32,
+    name: String,
+}
+
+async fn read_handler(
+    service: web::Data<CapService>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, CapServiceError> {
+    let id = OrdersId {
+        id: id.into_inner(),
+    };
+    let order = read_order_by_id(service.get_ref(), id, Capability::Read).await?;
+    Ok(HttpResponse::Ok().json(order))
+}
+
+async fn create_handler(
+    service: web::Data<CapService>,
+    order: web::Json<Orders>,
+) -> Result<HttpResponse, CapServiceError> {
+    let order = create_order(service.get_ref(), order.into_inner(), Capability::Create).await?;
+    Ok(HttpResponse::Created().json(order))
+}
+
+async fn update_handler(
+    service: web::Data<CapService>,
+    order: web::Json<Orders>,
+) -> Result<HttpResponse, CapServiceError> {
+    update_order(service.get_ref(), order.into_inner(), Capability::Update).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn delete_all_handler(
+    service: web::Data<CapService>,
+    orders: web::Json<Vec<Orders>>,
+) -> Result<HttpResponse, CapServiceError> {
+    delete_orders(
+        service.get_ref(),
+        orders.into_inner(),
+        Capability::DeleteAll,
+    )
+    .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn delete_handler(
+    service: web::Data<CapService>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, CapServiceError> {
+    // The caller only holds a read capability.
+    let id = OrdersId {
+        id: id.into_inner(),
+    };
+    delete_order_by_id(service.get_ref(), id, Capability::Read).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[service(WebService, name = "api")]
+#[actix_web::test]
+async fn client_calls_capability_server() {
+    let server_service = CapService::build().await.expect("Failed to setup service");
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(server_service.clone()))
+            .route("/api/orders", web::post().to(create_handler))
+            .route("/api/orders/{id}", web::get().to(read_handler))
+            .route("/api/orders/{id}", web::put().to(update_handler))
+            .route("/api/orders/{id}", web::delete().to(delete_handler))
+            .route("/api/orders", web::delete().to(delete_all_handler))
+    })
+    .bind(("127.0.0.1", 0))
+    .expect("Failed to bind test server");
+    let addr = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+
+    let config = WebServiceConfig::new().base_url(format!("http://{}/api", addr));
+    let service = CapService::build_with(config)
+        .await
+        .expect("Failed to setup service");
+    let client = OrdersClient::new(service.api);
+
+    let order = client
+        .read(OrdersId { id: 1 })
+        .await
+        .expect("Failed to read order");
+    assert_eq!(order.name, "Remote order");
+
+    let missing = client.read(OrdersId { id: 2 }).await;
+    assert!(matches!(missing, Err(CapServiceError::NotFound)));
+
+    let created = client
+        .create(Orders {
+            id: 3,
+            name: "New order".to_string(),
+        })
+        .await
+        .expect("Failed to create order");
+    assert_eq!(created.id, 3);
+
+    // The server answers without a body.
+    client
+        .update(Orders {
+            id: 3,
+            name: "Changed order".to_string(),
+        })
+        .await
+        .expect("Failed to update order");
+    client
+        .delete_all(vec![created])
+        .await
+        .expect("Failed to delete orders");
+
+    let denied = client.delete(OrdersId { id: 1 }).await;
+    assert!(matches!(
+        denied,
+        Err(CapServiceError::Denied {
+            required: Capability::Delete,
+            presented: Capability::Read,
+        })
+    ));
+}
+
+#[capability(Read, Orders)]
+fn read_order(order: Orders) -> Result<Orders, CapServiceError> {
+    Ok(order)
+}
+
+#[capability(Read, Orders, id = "i32")]
+fn read_order_by_id(order_id: OrdersId) -> Result<Orders, CapServiceError> {
+    match order_id.id {
+        1 => Ok(Orders {
+            id: 1,
+            name: "Remote order".to_string(),
+        }),
+        _ => Err(CapServiceError::NotFound),
+    }
+}
+
+#[capability(Create, Orders)]
+fn create_order(order: Orders) -> Result<Orders, CapServiceError> {
+    Ok(order)
+}
+
+#[capability(Update, Orders)]
+fn update_order(_order: Orders) -> Result<(), CapServiceError> {
+    Ok(())
+}
+
+#[capability(Update, Orders, id = "i32")]
+fn update_order_by_id(_order_id: OrdersId) -> Result<(), CapServiceError> {
+    Ok(())
+}
+
+#[capability(DeleteAll, Orders)]
+fn delete_orders(orders: Vec<Orders>) -> Result<(), CapServiceError> {
+    match orders.len() {
+        1 => Ok(()),
+        _ => Err(CapServiceError::NotFound),
+    }
+}
+
+#[capability(Delete, Orders)]
+fn delete_order(_order: Orders) -> Result<(), CapServiceError> {
+    Ok(())
+}
+
+#[capability(Delete, Orders, id = "i32")]
+fn delete_order_by_id(_order_id: OrdersId) -> Result<(), CapServiceError> {
+    Ok(())
+}
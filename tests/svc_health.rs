@@ -0,0 +1,51 @@
+use actix_web::{test, web, App};
+use capabilities::{readiness_route, PostgresDb, SqliteDb};
+use capabilities_derive::service;
+
+mod sqlite {
+    use super::*;
+
+    #[service(SqliteDb, name = "db", max_connections = 1)]
+    #[actix_web::test]
+    async fn report_readiness_until_closed() {
+        let service = CapService::build("sqlite::memory:".to_string())
+            .await
+            .expect("Failed to create database");
+        assert!(service.health().await.is_ok());
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(service.clone()))
+                .route("/ready", readiness_route::<CapService>()),
+        )
+        .await;
+
+        let request = test::TestRequest::get().uri("/ready").to_request();
+        let report: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(report["ready"], true);
+        assert_eq!(report["backends"][0]["name"], "db");
+
+        service.close().await;
+        assert!(service.health().await.is_err());
+
+        let request = test::TestRequest::get().uri("/ready").to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 503);
+    }
+}
+
+mod postgres {
+    use super::*;
+
+    // Nothing listens on port 1, so every attempt fails after the acquire timeout.
+    #[service(PostgresDb, name = "db", acquire_timeout = 1, connect_retries = 1)]
+    #[tokio::test]
+    async fn give_up_after_retrying() {
+        let service = CapService::builder()
+            .url("postgres://capabilities@127.0.0.1:1/orders")
+            .retry_backoff(std::time::Duration::from_millis(10))
+            .build()
+            .await;
+        assert!(service.is_err());
+    }
+}
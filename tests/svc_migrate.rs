@@ -0,0 +1,26 @@
+use capabilities::SqliteDb;
+use capabilities_derive::service;
+
+// Every connection to sqlite::memory: is its own database, so keep it to one.
+#[service(
+    SqliteDb,
+    name = "db",
+    max_connections = 1,
+    migrations = "tests/migrations"
+)]
+#[tokio::test]
+async fn build_runs_migrations() {
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+
+    sqlx::query("INSERT INTO orders (id, name) VALUES (1, 'Migrated order')")
+        .execute(&service.db)
+        .await
+        .expect("Missing orders table");
+
+    service
+        .migrate()
+        .await
+        .expect("Migrations are not idempotent");
+}
@@ -0,0 +1,97 @@
+use capabilities::{Create, Read, SqliteDb};
+use capabilities_derive::{capabilities, capability, service};
+
+#[capabilities(Read, Create, id = "id")]
+pub struct Orders {
+    id: i32,
+    name: String,
+}
+
+async fn setup(url: &str, name: &str) {
+    let service = CapService::build(url.to_string())
+        .await
+        .expect("Failed to create database");
+    sqlx::query("CREATE TABLE orders (id INTEGER PRIMARY KEY, name TEXT NOT NULL)")
+        .execute(&service.db)
+        .await
+        .expect("Failed to create orders");
+    sqlx::query("INSERT INTO orders (id, name) VALUES (1, ?)")
+        .bind(name)
+        .execute(&service.db)
+        .await
+        .expect("Failed to insert order");
+    service.db.close().await;
+}
+
+#[service(SqliteDb, name = "db")]
+#[tokio::test]
+async fn reads_go_to_the_replica() {
+    let dir = std::env::temp_dir().join("capabilities_replica");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Failed to create directory");
+    let primary = format!("sqlite://{}?mode=rwc", dir.join("primary.db").display());
+    let replica = format!("sqlite://{}?mode=rwc", dir.join("replica.db").display());
+    setup(&primary, "primary").await;
+    setup(&replica, "replica").await;
+
+    let service = CapService::build_with_replica(primary, replica)
+        .await
+        .expect("Failed to connect");
+
+    let order = read_order_by_id(&service, OrdersId { id: 1 }, Capability::Read)
+        .await
+        .expect("Failed to read order");
+    assert_eq!(order.name, "replica");
+
+    let order = read_order(&service, order, Capability::Read)
+        .await
+        .expect("Failed to read order");
+    assert_eq!(order.name, "primary");
+
+    let order = Orders {
+        id: 2,
+        name: "created".to_string(),
+    };
+    create_order(&service, order, Capability::Create)
+        .await
+        .expect("Failed to create order");
+    let created: (String,) = sqlx::query_as("SELECT name FROM orders WHERE id = 2")
+        .fetch_one(&service.db)
+        .await
+        .expect("Order was not created on the primary");
+    assert_eq!(created.0, "created");
+
+    std::fs::remove_dir_all(dir).expect("Failed to remove directory");
+}
+
+// Reading what was just written must not lag behind on the replica.
+#[capability(Read, Orders, route = "primary")]
+fn read_order(order: Orders) -> Result<Orders, CapServiceError> {
+    let (name,): (String,) = sqlx::query_as("SELECT name FROM orders WHERE id = ?")
+        .bind(order.id)
+        .fetch_one(&self.db)
+        .await?;
+    Ok(Orders { id: order.id, name })
+}
+
+#[capability(Read, Orders, id = "i32")]
+fn read_order_by_id(order_id: OrdersId) -> Result<Orders, CapServiceError> {
+    let (name,): (String,) = sqlx::query_as("SELECT name FROM orders WHERE id = ?")
+        .bind(order_id.id)
+        .fetch_one(&self.db)
+        .await?;
+    Ok(Orders {
+        id: order_id.id,
+        name,
+    })
+}
+
+#[capability(Create, Orders)]
+fn create_order(order: Orders) -> Result<Orders, CapServiceError> {
+    sqlx::query("INSERT INTO orders (id, name) VALUES (?, ?)")
+        .bind(order.id)
+        .bind(&order.name)
+        .execute(&self.db)
+        .await?;
+    Ok(order)
+}
@@ -0,0 +1,104 @@
+use capabilities::{Create, SqliteDb, Update};
+use capabilities_derive::{capabilities, capability, service};
+
+#[capabilities(Create, id = "id")]
+pub struct Orders {
+    id: i32,
+    customer: i32,
+}
+
+#[capabilities(Update, id = "id")]
+pub struct Customer {
+    id: i32,
+    orders: i32,
+}
+
+async fn count_orders(service: &CapService) -> i64 {
+    let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM orders")
+        .fetch_one(&service.db)
+        .await
+        .expect("Failed to count orders");
+    count.0
+}
+
+// Every connection to sqlite::memory: is its own database, so keep it to one.
+#[service(SqliteDb, name = "db", max_connections = 1)]
+#[tokio::test]
+async fn capabilities_commit_or_roll_back_together() {
+    let service = CapService::build("sqlite::memory:".to_string())
+        .await
+        .expect("Failed to create database");
+    sqlx::query("CREATE TABLE orders (id INTEGER PRIMARY KEY, customer INTEGER NOT NULL)")
+        .execute(&service.db)
+        .await
+        .expect("Failed to create orders");
+    sqlx::query("CREATE TABLE customers (id INTEGER PRIMARY KEY, orders INTEGER NOT NULL)")
+        .execute(&service.db)
+        .await
+        .expect("Failed to create customers");
+    sqlx::query("INSERT INTO customers (id, orders) VALUES (7, 0)")
+        .execute(&service.db)
+        .await
+        .expect("Failed to create customer");
+
+    service
+        .transaction(|tx| async move {
+            assert!(tx.in_transaction());
+            create_order(&tx, Orders { id: 1, customer: 7 }, Capability::Create).await?;
+            update_customer(&tx, Customer { id: 7, orders: 1 }, Capability::Update).await
+        })
+        .await
+        .expect("Failed to commit");
+    assert_eq!(count_orders(&service).await, 1);
+
+    // The update is denied, so the order created before it is rolled back.
+    let denied = service
+        .transaction(|tx| async move {
+            create_order(&tx, Orders { id: 2, customer: 7 }, Capability::Create).await?;
+            update_customer(&tx, Customer { id: 7, orders: 2 }, Capability::Read).await
+        })
+        .await;
+    assert!(matches!(denied, Err(CapServiceError::Denied { .. })));
+    assert_eq!(count_orders(&service).await, 1);
+
+    // Writes of the body itself are rolled back with the capabilities.
+    let failed: Result<(), CapServiceError> = service
+        .transaction(|tx| async move {
+            let mut con = tx.connection().await?;
+            sqlx::query("INSERT INTO orders (id, customer) VALUES (3, 7)")
+                .execute(&mut *con)
+                .await?;
+            Err(CapServiceError::NotFound)
+        })
+        .await;
+    assert!(matches!(failed, Err(CapServiceError::NotFound)));
+    assert_eq!(count_orders(&service).await, 1);
+    assert!(!service.in_transaction());
+}
+
+#[capability(Create, Orders)]
+fn create_order(order: Orders) -> Result<Orders, CapServiceError> {
+    let mut con = self.connection().await?;
+    sqlx::query("INSERT INTO orders (id, customer) VALUES (?, ?)")
+        .bind(order.id)
+        .bind(order.customer)
+        .execute(&mut *con)
+        .await?;
+    Ok(order)
+}
+
+#[capability(Update, Customer)]
+fn update_customer(customer: Customer) -> Result<(), CapServiceError> {
+    let mut con = self.connection().await?;
+    sqlx::query("UPDATE customers SET orders = ? WHERE id = ?")
+        .bind(customer.orders)
+        .bind(customer.id)
+        .execute(&mut *con)
+        .await?;
+    Ok(())
+}
+
+#[capability(Update, Customer, id = "i32")]
+fn update_customer_by_id(_customer_id: CustomerId) -> Result<(), CapServiceError> {
+    Ok(())
+}